@@ -0,0 +1,1915 @@
+use std::{collections::HashSet, time::Instant};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use tui::widgets::TableState;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    doctor::long_running_timers,
+    find_overlaps, format_duration, format_timestamp,
+    history::{History, Operation},
+    idle::{Away, IdleTracker},
+    parse_timestamp,
+    period::{parse_date, Period, Preset},
+    report::{archive_report, Report, Summary},
+    search_notes,
+    storage::{Data, Repository},
+    time_zone::{local_day, today},
+    timesheet::Timesheet,
+    toggle_timer,
+    trash::TrashedTask,
+    validate_project_name, Error, Subtask, Task, TimeFrame,
+};
+
+/// How long a toast stays visible.
+const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often the last input is saved with the running timers.
+const ACTIVITY_SAVE_MINUTES: i64 = 5;
+
+/// Options of the popup for a timer left running, in order.
+pub const STOP_CHOICES: [StopChoice; 3] = [
+    StopChoice::LastActivity,
+    StopChoice::ChosenTime,
+    StopChoice::Keep,
+];
+
+/// Periods offered by the report popup, in order.
+pub const REPORT_RANGES: [ReportRange; 7] = [
+    ReportRange::AllTime,
+    ReportRange::Preset(Preset::Today),
+    ReportRange::Preset(Preset::ThisWeek),
+    ReportRange::Preset(Preset::LastWeek),
+    ReportRange::Preset(Preset::ThisMonth),
+    ReportRange::Preset(Preset::LastMonth),
+    ReportRange::Custom,
+];
+
+pub struct App {
+    pub state: State,
+    pub tasks: Vec<Task>,
+    pub trash: Vec<TrashedTask>,
+    pub task_list_state: TableState,
+    pub time_frame_list_state: TableState,
+    pub timesheet_list_state: TableState,
+    pub archive_list_state: TableState,
+    pub trash_list_state: TableState,
+    pub search_list_state: TableState,
+    pub config: Config,
+    /// Only projects with this tag are shown in the projects table
+    pub tag_filter: Option<String>,
+    /// Projects whose subtasks are shown below them in the projects table
+    expanded: HashSet<Uuid>,
+    /// Message shown in place of the footer for a few seconds
+    pub toast: Option<Toast>,
+    repository: Repository,
+    /// Changes made in this session, for undo and redo
+    history: History,
+    idle: IdleTracker,
+    /// Timers found running for too long on startup that are still to be asked about, last
+    /// first
+    long_running: Vec<Uuid>,
+}
+
+pub struct Toast {
+    pub message: String,
+    pub is_error: bool,
+    shown_at: Instant,
+}
+
+pub enum State {
+    Projects,
+    Help,
+    CreateProject {
+        input: String,
+        error: Option<String>,
+    },
+    EditProject {
+        task_id: Uuid,
+        form: ProjectForm,
+    },
+    DeleteProject,
+    /// Adding a subtask to a project, or renaming one if `subtask_id` is set
+    EditSubtask {
+        task_id: Uuid,
+        subtask_id: Option<Uuid>,
+        input: String,
+        error: Option<String>,
+    },
+    DeleteSubtask {
+        task_id: Uuid,
+        subtask_id: Uuid,
+    },
+    ArchivedProjects,
+    Trash,
+    PurgeTrashed,
+    TaskDetails {
+        task_id: Uuid,
+    },
+    EditTimeFrame {
+        task_id: Uuid,
+        form: TimeFrameForm,
+    },
+    DeleteTimeFrame {
+        task_id: Uuid,
+    },
+    /// Entering the note of a time frame, either right after its timer was stopped or from the
+    /// details view
+    EditNote {
+        task_id: Uuid,
+        time_frame_id: Uuid,
+        input: String,
+        in_details: bool,
+    },
+    SearchNotes {
+        query: String,
+    },
+    Report {
+        form: ReportForm,
+    },
+    Timesheet {
+        period: Period,
+    },
+    /// Asking whether to stop a timer found running for too long on startup
+    LongRunningTimer {
+        task_id: Uuid,
+        form: StopTimerForm,
+    },
+    /// Asking what to do with the time the user was away while a timer was running, on top of
+    /// the state they left
+    Idle {
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        previous: Box<State>,
+    },
+}
+
+/// What to do with the time away from a running timer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IdleChoice {
+    /// Count it as worked
+    Keep,
+    Discard,
+    /// Record it as a separate time frame, to be edited or deleted later
+    Split,
+}
+
+/// Input of the popup for editing the name, client and tags of a project.
+pub struct ProjectForm {
+    pub name: String,
+    pub client: String,
+    /// Comma separated
+    pub tags: String,
+    pub focus: ProjectField,
+    pub error: Option<String>,
+}
+
+#[derive(PartialEq, Eq)]
+pub enum ProjectField {
+    Name,
+    Client,
+    Tags,
+}
+
+/// Row of the projects table.
+pub enum ProjectRow<'a> {
+    Project { task: &'a Task, expanded: bool },
+    Subtask(&'a Task, &'a Subtask),
+}
+
+/// Input of the popup for adding or editing a time frame.
+pub struct TimeFrameForm {
+    /// `None` when adding a new time frame
+    pub time_frame_id: Option<Uuid>,
+    /// Chosen with up and down among the subtasks of the project
+    pub subtask_id: Uuid,
+    pub start: String,
+    pub end: String,
+    pub focus: FormField,
+    pub error: Option<String>,
+}
+
+/// Input of the popup for a timer left running.
+pub struct StopTimerForm {
+    /// Index into `STOP_CHOICES`
+    pub selected: usize,
+    /// Time to stop the timer at, starting out as its last activity
+    pub stop_at: String,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StopChoice {
+    LastActivity,
+    ChosenTime,
+    Keep,
+}
+
+/// Input of the popup for choosing the period of a report.
+pub struct ReportForm {
+    /// Index into `REPORT_RANGES`
+    pub selected: usize,
+    /// First and last day of a custom range
+    pub from: String,
+    pub to: String,
+    pub focus: FormField,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReportRange {
+    AllTime,
+    Preset(Preset),
+    Custom,
+}
+
+#[derive(PartialEq, Eq)]
+pub enum FormField {
+    Start,
+    End,
+}
+
+pub enum Transitions {
+    CreateNew,
+    CreateSubtask,
+    Delete,
+    Edit,
+    EditNote,
+    Escape,
+    Expand,
+    Collapse,
+    NextField,
+    ShowTagFilter,
+    ShowArchived,
+    ShowTrash,
+    ShowDetails,
+    ShowHelp,
+    ShowReport,
+    ShowSearch,
+    ShowTimesheet,
+    InputCharacter(char),
+}
+
+impl App {
+    pub fn load(mut repository: Repository, config: Config) -> Result<Self, Error> {
+        let Data { tasks, trash } = repository.load()?;
+
+        let mut task_list_state = TableState::default();
+        task_list_state.select(Some(0));
+        let idle = IdleTracker::new(&config.idle);
+        let long_running = long_running_timers(&tasks, &config, Utc::now())
+            .iter()
+            .rev()
+            .map(|task| task.id)
+            .collect();
+
+        let mut app = App {
+            state: State::Projects,
+            tasks,
+            trash,
+            task_list_state,
+            time_frame_list_state: TableState::default(),
+            timesheet_list_state: TableState::default(),
+            archive_list_state: TableState::default(),
+            trash_list_state: TableState::default(),
+            search_list_state: TableState::default(),
+            config,
+            tag_filter: None,
+            expanded: HashSet::new(),
+            toast: None,
+            repository,
+            history: History::default(),
+            idle,
+            long_running,
+        };
+        app.show_next_long_running();
+
+        Ok(app)
+    }
+
+    pub fn transition(&mut self, transition: Transitions) {
+        match (&mut self.state, transition) {
+            (State::Projects, Transitions::CreateNew) => {
+                self.state = State::CreateProject {
+                    input: String::new(),
+                    error: None,
+                }
+            }
+            (State::Projects, Transitions::Delete) => {
+                self.state = match self.selected_row() {
+                    Some(ProjectRow::Subtask(task, subtask)) => State::DeleteSubtask {
+                        task_id: task.id,
+                        subtask_id: subtask.id,
+                    },
+                    _ => State::DeleteProject,
+                };
+            }
+            (State::Projects, Transitions::CreateSubtask) => {
+                if let Some(task_id) = self.selected_id() {
+                    self.state = State::EditSubtask {
+                        task_id,
+                        subtask_id: None,
+                        input: String::new(),
+                        error: None,
+                    };
+                }
+            }
+            (State::EditSubtask { input, error, .. }, Transitions::InputCharacter(character)) => {
+                input.push(character);
+                *error = None;
+            }
+            (State::EditSubtask { input, error, .. }, Transitions::Delete) => {
+                input.pop();
+                *error = None;
+            }
+            (State::EditSubtask { .. } | State::DeleteSubtask { .. }, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::Projects, Transitions::Expand) => {
+                if let Some(task_id) = self.selected_id() {
+                    self.expanded.insert(task_id);
+                }
+            }
+            (State::Projects, Transitions::Collapse) => {
+                if let Some(task_id) = self.selected_id() {
+                    self.expanded.remove(&task_id);
+                    self.select_project(task_id);
+                }
+            }
+            (State::Projects, Transitions::ShowReport) => {
+                // Custom ranges start out as the current month, the usual invoicing period
+                let month = Period::month(today(self.config.time_zone()));
+                self.state = State::Report {
+                    form: ReportForm {
+                        selected: 0,
+                        from: month.first_day.to_string(),
+                        to: month.last_day.to_string(),
+                        focus: FormField::Start,
+                        error: None,
+                    },
+                };
+            }
+            (State::Report { form }, Transitions::InputCharacter(character))
+                if form.range() == ReportRange::Custom =>
+            {
+                form.focused_input().push(character);
+            }
+            (State::Report { form }, Transitions::Delete)
+                if form.range() == ReportRange::Custom =>
+            {
+                form.focused_input().pop();
+            }
+            (State::Report { form }, Transitions::NextField) => {
+                form.focus = match form.focus {
+                    FormField::Start => FormField::End,
+                    FormField::End => FormField::Start,
+                };
+            }
+            (State::Report { .. }, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::Projects, Transitions::ShowTimesheet) => {
+                self.state = State::Timesheet {
+                    period: Period::week(today(self.config.time_zone())),
+                };
+                self.timesheet_list_state.select(Some(0));
+            }
+            (State::Timesheet { .. }, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::Projects, Transitions::ShowHelp) => {
+                self.state = State::Help;
+            }
+            (State::Help, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::CreateProject { input, error }, Transitions::InputCharacter(character)) => {
+                input.push(character);
+                *error = None;
+            }
+            (State::CreateProject { input, error }, Transitions::Delete) => {
+                input.pop();
+                *error = None;
+            }
+            (State::CreateProject { .. }, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::DeleteProject, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::Projects, Transitions::Edit) => match self.selected_row() {
+                Some(ProjectRow::Subtask(task, subtask)) => {
+                    self.state = State::EditSubtask {
+                        task_id: task.id,
+                        subtask_id: Some(subtask.id),
+                        input: subtask.name.clone(),
+                        error: None,
+                    };
+                }
+                Some(ProjectRow::Project { task, .. }) => {
+                    self.state = State::EditProject {
+                        task_id: task.id,
+                        form: ProjectForm {
+                            name: task.project.clone(),
+                            client: task.client.clone().unwrap_or_default(),
+                            tags: task.tags.join(", "),
+                            focus: ProjectField::Name,
+                            error: None,
+                        },
+                    };
+                }
+                None => {}
+            },
+            (State::EditProject { form, .. }, Transitions::InputCharacter(character)) => {
+                form.focused_input().push(character);
+                form.error = None;
+            }
+            (State::EditProject { form, .. }, Transitions::Delete) => {
+                form.focused_input().pop();
+                form.error = None;
+            }
+            (State::EditProject { form, .. }, Transitions::NextField) => {
+                form.focus = match form.focus {
+                    ProjectField::Name => ProjectField::Client,
+                    ProjectField::Client => ProjectField::Tags,
+                    ProjectField::Tags => ProjectField::Name,
+                };
+            }
+            (State::EditProject { .. }, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::Projects, Transitions::ShowTagFilter) => {
+                self.tag_filter = self.next_tag_filter();
+                self.clamp_selection();
+            }
+            (State::Projects, Transitions::ShowArchived) => {
+                self.state = State::ArchivedProjects;
+                self.archive_list_state.select(Some(0));
+            }
+            (State::ArchivedProjects, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::Projects, Transitions::ShowTrash) => {
+                self.state = State::Trash;
+                self.trash_list_state.select(Some(0));
+            }
+            (State::Trash, Transitions::Delete) => {
+                if self.selected_trashed().is_some() {
+                    self.state = State::PurgeTrashed;
+                }
+            }
+            (State::Trash, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::PurgeTrashed, Transitions::Escape) => {
+                self.state = State::Trash;
+            }
+            (State::Projects, Transitions::ShowDetails) => {
+                if let Some(task_id) = self.selected_id() {
+                    self.state = State::TaskDetails { task_id };
+                    self.select_last_time_frame();
+                }
+            }
+            (State::TaskDetails { .. }, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::TaskDetails { task_id }, Transitions::CreateNew) => {
+                let task_id = *task_id;
+                let now = format_timestamp(Utc::now(), self.config.time_zone());
+                let Some(subtask_id) = self.detail_task().map(Task::current_subtask) else {
+                    return;
+                };
+                self.state = State::EditTimeFrame {
+                    task_id,
+                    form: TimeFrameForm {
+                        time_frame_id: None,
+                        subtask_id,
+                        start: now.clone(),
+                        end: now,
+                        focus: FormField::Start,
+                        error: None,
+                    },
+                };
+            }
+            (State::TaskDetails { task_id }, Transitions::Edit) => {
+                let task_id = *task_id;
+                let time_zone = self.config.time_zone();
+                if let Some(time_frame) = self.selected_time_frame() {
+                    self.state = State::EditTimeFrame {
+                        task_id,
+                        form: TimeFrameForm {
+                            time_frame_id: Some(time_frame.id),
+                            subtask_id: time_frame.subtask_id,
+                            start: format_timestamp(time_frame.start_time, time_zone),
+                            end: format_timestamp(time_frame.end_time, time_zone),
+                            focus: FormField::Start,
+                            error: None,
+                        },
+                    };
+                }
+            }
+            (State::TaskDetails { task_id }, Transitions::Delete) => {
+                let task_id = *task_id;
+                if self.selected_time_frame().is_some() {
+                    self.state = State::DeleteTimeFrame { task_id };
+                }
+            }
+            (State::EditTimeFrame { form, .. }, Transitions::InputCharacter(character)) => {
+                form.focused_input().push(character);
+            }
+            (State::EditTimeFrame { form, .. }, Transitions::Delete) => {
+                form.focused_input().pop();
+            }
+            (State::EditTimeFrame { form, .. }, Transitions::NextField) => {
+                form.focus = match form.focus {
+                    FormField::Start => FormField::End,
+                    FormField::End => FormField::Start,
+                };
+            }
+            (
+                State::EditTimeFrame { task_id, .. } | State::DeleteTimeFrame { task_id },
+                Transitions::Escape,
+            ) => {
+                self.state = State::TaskDetails { task_id: *task_id };
+            }
+            (State::TaskDetails { task_id }, Transitions::EditNote) => {
+                let task_id = *task_id;
+                if let Some(time_frame) = self.selected_time_frame() {
+                    self.state = State::EditNote {
+                        task_id,
+                        time_frame_id: time_frame.id,
+                        input: time_frame.note.clone().unwrap_or_default(),
+                        in_details: true,
+                    };
+                }
+            }
+            (State::EditNote { input, .. }, Transitions::InputCharacter(character)) => {
+                input.push(character);
+            }
+            (State::EditNote { input, .. }, Transitions::Delete) => {
+                input.pop();
+            }
+            (
+                State::EditNote {
+                    task_id,
+                    in_details,
+                    ..
+                },
+                Transitions::Escape,
+            ) => {
+                self.state = if *in_details {
+                    State::TaskDetails { task_id: *task_id }
+                } else {
+                    State::Projects
+                };
+            }
+            (State::Projects, Transitions::ShowSearch) => {
+                self.state = State::SearchNotes {
+                    query: String::new(),
+                };
+                self.search_list_state.select(Some(0));
+            }
+            (State::SearchNotes { query }, Transitions::InputCharacter(character)) => {
+                query.push(character);
+                self.search_list_state.select(Some(0));
+            }
+            (State::SearchNotes { query }, Transitions::Delete) => {
+                query.pop();
+                self.search_list_state.select(Some(0));
+            }
+            (State::SearchNotes { .. }, Transitions::Escape) => {
+                self.state = State::Projects;
+            }
+            (State::LongRunningTimer { form, .. }, Transitions::InputCharacter(character))
+                if form.choice() == StopChoice::ChosenTime =>
+            {
+                form.stop_at.push(character);
+                form.error = None;
+            }
+            (State::LongRunningTimer { form, .. }, Transitions::Delete)
+                if form.choice() == StopChoice::ChosenTime =>
+            {
+                form.stop_at.pop();
+                form.error = None;
+            }
+            (State::LongRunningTimer { .. }, Transitions::Escape) => {
+                self.show_next_long_running();
+            }
+            (_, _) => {}
+        }
+    }
+
+    /// Re-reads the task list if the DB file was changed by another process.
+    pub fn reload_if_changed(&mut self) -> Result<(), Error> {
+        if self.repository.has_changed() {
+            let data = self.repository.load()?;
+            self.set_data(data);
+            self.clamp_selection();
+            self.clamp_archive_selection();
+            self.clamp_trash_selection();
+            self.clamp_time_frame_selection();
+        }
+
+        Ok(())
+    }
+
+    /// Projects shown in the projects table, which leaves out archived ones and those without
+    /// the tag filtered by.
+    pub fn active_tasks(&self) -> Vec<&Task> {
+        self.tasks
+            .iter()
+            .filter(|task| !task.archived)
+            .filter(|task| {
+                self.tag_filter
+                    .as_ref()
+                    .is_none_or(|filter| task.tags.contains(filter))
+            })
+            .collect()
+    }
+
+    pub fn archived_tasks(&self) -> Vec<&Task> {
+        self.tasks.iter().filter(|task| task.archived).collect()
+    }
+
+    /// Rows of the projects table: the projects of `active_tasks`, each followed by its
+    /// subtasks if expanded.
+    pub fn project_rows(&self) -> Vec<ProjectRow<'_>> {
+        let mut rows = vec![];
+        for task in self.active_tasks() {
+            let expanded = self.expanded.contains(&task.id);
+            rows.push(ProjectRow::Project { task, expanded });
+            if expanded {
+                rows.extend(
+                    task.subtasks
+                        .iter()
+                        .map(|subtask| ProjectRow::Subtask(task, subtask)),
+                );
+            }
+        }
+
+        rows
+    }
+
+    pub fn select_next(&mut self) {
+        let count = self.project_rows().len();
+        move_selection(&mut self.task_list_state, count, 1);
+    }
+
+    pub fn select_previous(&mut self) {
+        let count = self.project_rows().len();
+        move_selection(&mut self.task_list_state, count, -1);
+    }
+
+    pub fn select_next_archived(&mut self) {
+        let count = self.archived_tasks().len();
+        move_selection(&mut self.archive_list_state, count, 1);
+    }
+
+    pub fn select_previous_archived(&mut self) {
+        let count = self.archived_tasks().len();
+        move_selection(&mut self.archive_list_state, count, -1);
+    }
+
+    pub fn select_next_trashed(&mut self) {
+        move_selection(&mut self.trash_list_state, self.trash.len(), 1);
+    }
+
+    pub fn select_previous_trashed(&mut self) {
+        move_selection(&mut self.trash_list_state, self.trash.len(), -1);
+    }
+
+    pub fn toggle_selected(&mut self) -> Result<(), Error> {
+        let Some(selected_id) = self.selected_id() else {
+            return Ok(());
+        };
+
+        let subtask_id = self.selected_subtask_id();
+        let merge_gap = self.config.merge_gap_on_stop();
+        let mut operation = None;
+        let mut stopped = None;
+        self.update(|tasks| {
+            if let Some(selected) = tasks.iter().position(|task| task.id == selected_id) {
+                let before = tasks.clone();
+                // The project row toggles the subtask that was tracked last
+                let subtask_id = subtask_id.unwrap_or_else(|| tasks[selected].current_subtask());
+                stopped = toggle_timer(tasks, selected, subtask_id, merge_gap);
+                operation = Operation::toggle_timer(&before, tasks);
+            }
+        })?;
+        self.record(operation);
+
+        // Ask what was done, which can be skipped with escape
+        if let Some((task_id, time_frame_id)) = stopped {
+            let note = self
+                .tasks
+                .iter()
+                .find(|task| task.id == task_id)
+                .and_then(|task| {
+                    task.times
+                        .iter()
+                        .find(|time_frame| time_frame.id == time_frame_id)
+                })
+                .and_then(|time_frame| time_frame.note.clone());
+            self.state = State::EditNote {
+                task_id,
+                time_frame_id,
+                input: note.unwrap_or_default(),
+                in_details: false,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Creates the project entered in the create popup. Empty and taken names are reported in
+    /// the popup instead of failing.
+    pub fn create_project(&mut self) -> Result<(), Error> {
+        let State::CreateProject { input, error } = &mut self.state else {
+            return Ok(());
+        };
+        let project = match validate_project_name(&self.tasks, input, None) {
+            Ok(project) => project,
+            Err(e) => {
+                *error = Some(e.to_string());
+                return Ok(());
+            }
+        };
+        self.state = State::Projects;
+
+        let task = Task::new(project);
+        let operation = Operation::CreateProject { task: task.clone() };
+
+        self.update(|tasks| tasks.push(task))?;
+        self.record(Some(operation));
+
+        Ok(())
+    }
+
+    pub fn delete_selected(&mut self) -> Result<(), Error> {
+        let Some(selected_id) = self.selected_id() else {
+            return Ok(());
+        };
+
+        let merge_gap = self.config.merge_gap_on_stop();
+        let mut operation = None;
+        self.update_data(|data| {
+            if let Some(position) = data.trash_task(selected_id, merge_gap) {
+                let trashed = data.trash.last().cloned().expect("was just added");
+                operation = Some(Operation::DeleteProject { trashed, position });
+            }
+        })?;
+        self.record(operation);
+        self.clamp_selection();
+
+        Ok(())
+    }
+
+    /// Moves the project selected in the trash view back to the end of the projects table,
+    /// unless another project took its name in the meantime.
+    pub fn restore_selected_trashed(&mut self) -> Result<(), Error> {
+        let Some(selected_id) = self.selected_trashed().map(|trashed| trashed.task.id) else {
+            return Ok(());
+        };
+
+        let mut result = Ok(());
+        self.update_data(|data| {
+            if let Some(position) = data
+                .trash
+                .iter()
+                .position(|trashed| trashed.task.id == selected_id)
+            {
+                let task = &data.trash[position].task;
+                result =
+                    validate_project_name(&data.tasks, &task.project, Some(task.id)).map(|_| ());
+                if result.is_ok() {
+                    let trashed = data.trash.remove(position);
+                    data.tasks.push(trashed.task);
+                }
+            }
+        })?;
+        result?;
+        self.clamp_trash_selection();
+
+        if let Some(task) = self.tasks.iter().find(|task| task.id == selected_id) {
+            self.toast = Some(Toast::info(format!("Restored {}", task.project)));
+        }
+
+        Ok(())
+    }
+
+    /// Permanently deletes the project selected in the trash view.
+    pub fn purge_selected_trashed(&mut self) -> Result<(), Error> {
+        let Some(selected_id) = self.selected_trashed().map(|trashed| trashed.task.id) else {
+            return Ok(());
+        };
+
+        self.update_data(|data| data.trash.retain(|trashed| trashed.task.id != selected_id))?;
+        self.state = State::Trash;
+        self.clamp_trash_selection();
+
+        Ok(())
+    }
+
+    /// Adds the subtask entered in the subtask popup or renames the one edited. Empty and taken
+    /// names are reported in the popup instead of failing.
+    pub fn save_subtask(&mut self) -> Result<(), Error> {
+        let State::EditSubtask {
+            task_id,
+            subtask_id,
+            input,
+            error,
+        } = &mut self.state
+        else {
+            return Ok(());
+        };
+        let (task_id, subtask_id) = (*task_id, *subtask_id);
+        let name = input.trim().to_owned();
+
+        let Some(task) = self.tasks.iter().find(|task| task.id == task_id) else {
+            self.state = State::Projects;
+            return Ok(());
+        };
+        if name.is_empty() {
+            *error = Some(Error::EmptySubtaskName.to_string());
+            return Ok(());
+        }
+        if task
+            .subtasks
+            .iter()
+            .any(|subtask| Some(subtask.id) != subtask_id && subtask.name == name)
+        {
+            *error = Some(Error::SubtaskExists(task.project.clone(), name).to_string());
+            return Ok(());
+        }
+
+        self.update_task(task_id, |task| match subtask_id {
+            Some(subtask_id) => {
+                if let Some(subtask) = task
+                    .subtasks
+                    .iter_mut()
+                    .find(|subtask| subtask.id == subtask_id)
+                {
+                    subtask.name = name;
+                }
+            }
+            None => {
+                let _ = task.add_subtask(name);
+            }
+        })?;
+        self.state = State::Projects;
+        // Show where a new subtask went
+        self.expanded.insert(task_id);
+
+        Ok(())
+    }
+
+    /// Deletes the subtask chosen in the confirmation popup, moving its time frames to the
+    /// first remaining subtask. The last subtask of a project is kept, as time is always tracked
+    /// on a subtask.
+    pub fn delete_subtask(&mut self) -> Result<(), Error> {
+        let State::DeleteSubtask {
+            task_id,
+            subtask_id,
+        } = self.state
+        else {
+            return Ok(());
+        };
+        self.state = State::Projects;
+
+        let merge_gap = self.config.merge_gap_on_stop();
+        let mut result = Ok(());
+        self.update_task(task_id, |task| {
+            result = task.delete_subtask(subtask_id, merge_gap);
+        })?;
+        if let Err(e) = result {
+            self.show_error(&e);
+        }
+        self.clamp_selection();
+
+        Ok(())
+    }
+
+    /// Number of time frames of the subtask in the delete popup and the name of the subtask they
+    /// move to, or `None` if it is the last one.
+    pub fn subtask_deletion(&self) -> Option<(usize, &str)> {
+        let State::DeleteSubtask {
+            task_id,
+            subtask_id,
+        } = self.state
+        else {
+            return None;
+        };
+        let task = self.tasks.iter().find(|task| task.id == task_id)?;
+        let remaining = task
+            .subtasks
+            .iter()
+            .find(|subtask| subtask.id != subtask_id)?;
+        let count = task
+            .times
+            .iter()
+            .filter(|time_frame| time_frame.subtask_id == subtask_id)
+            .count();
+
+        Some((count, remaining.name.as_str()))
+    }
+
+    /// Reverts the most recent change made in this session.
+    pub fn undo(&mut self) -> Result<(), Error> {
+        let Some(operation) = self.history.undo() else {
+            self.toast = Some(Toast::info("Nothing to undo".to_owned()));
+            return Ok(());
+        };
+
+        // Checked against the stored data, which may have been changed by another process
+        let mut reverted = Ok(false);
+        self.update_data(|data| {
+            if operation.can_revert(data) {
+                reverted = operation.revert(data).map(|()| true);
+            }
+        })?;
+        if !reverted? {
+            self.toast = Some(Toast::error(format!(
+                "Cannot undo {}, as it was changed since",
+                operation.description()
+            )));
+            return Ok(());
+        }
+
+        self.toast = Some(Toast::info(format!("Undid {}", operation.description())));
+        self.history.undone(operation);
+        self.after_history_change();
+
+        Ok(())
+    }
+
+    /// Reapplies the most recently undone change.
+    pub fn redo(&mut self) -> Result<(), Error> {
+        let Some(operation) = self.history.redo() else {
+            self.toast = Some(Toast::info("Nothing to redo".to_owned()));
+            return Ok(());
+        };
+
+        let mut applied = Ok(false);
+        self.update_data(|data| {
+            if operation.can_apply(data) {
+                applied = operation.apply(data).map(|()| true);
+            }
+        })?;
+        if !applied? {
+            self.toast = Some(Toast::error(format!(
+                "Cannot redo {}, as it was changed since",
+                operation.description()
+            )));
+            return Ok(());
+        }
+
+        self.toast = Some(Toast::info(format!("Redid {}", operation.description())));
+        self.history.redone(operation);
+        self.after_history_change();
+
+        Ok(())
+    }
+
+    /// Keeps the selections valid and leaves the details view if its project is gone.
+    fn after_history_change(&mut self) {
+        if let State::TaskDetails { .. } = self.state {
+            if self.detail_task().is_none() {
+                self.state = State::Projects;
+            }
+        }
+
+        self.clamp_selection();
+        self.clamp_archive_selection();
+        self.clamp_trash_selection();
+        self.clamp_time_frame_selection();
+    }
+
+    /// Saves the name, client and tags entered in the project popup. Empty and taken names
+    /// are reported in the popup instead of failing.
+    pub fn save_project(&mut self) -> Result<(), Error> {
+        let State::EditProject { task_id, form } = &mut self.state else {
+            return Ok(());
+        };
+        let task_id = *task_id;
+        let project = match validate_project_name(&self.tasks, &form.name, Some(task_id)) {
+            Ok(project) => project,
+            Err(e) => {
+                form.error = Some(e.to_string());
+                return Ok(());
+            }
+        };
+
+        let client = Some(form.client.trim().to_owned()).filter(|client| !client.is_empty());
+        let tags = parse_tags(&form.tags);
+        self.update_task(task_id, |task| {
+            task.project = project;
+            task.client = client;
+            task.tags = tags;
+        })?;
+        self.state = State::Projects;
+        // The project may no longer have the tag filtered by
+        self.clamp_selection();
+
+        Ok(())
+    }
+
+    /// All tags of projects in the projects table, sorted.
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<_> = self
+            .tasks
+            .iter()
+            .filter(|task| !task.archived)
+            .flat_map(|task| task.tags.iter().map(String::as_str))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+
+        tags
+    }
+
+    /// The tag following the current filter in `tags`, or `None` to show all projects after the
+    /// last one.
+    fn next_tag_filter(&self) -> Option<String> {
+        let tags = self.tags();
+        let next = match &self.tag_filter {
+            Some(filter) => tags
+                .iter()
+                .position(|tag| tag == filter)
+                .and_then(|index| tags.get(index + 1)),
+            None => tags.first(),
+        };
+
+        next.map(|tag| tag.to_string())
+    }
+
+    /// Hides the selected project from the projects table, stopping its timer.
+    pub fn archive_selected(&mut self) -> Result<(), Error> {
+        let Some(selected_id) = self.selected_id() else {
+            return Ok(());
+        };
+
+        let merge_gap = self.config.merge_gap_on_stop();
+        self.update_task(selected_id, |task| {
+            task.stop(merge_gap);
+            task.archived = true;
+        })?;
+        self.clamp_selection();
+
+        if let Some(task) = self.tasks.iter().find(|task| task.id == selected_id) {
+            self.toast = Some(Toast::info(format!("Archived {}", task.project)));
+        }
+
+        Ok(())
+    }
+
+    /// Moves the project selected in the archived projects view back to the projects table.
+    pub fn restore_selected_archived(&mut self) -> Result<(), Error> {
+        let Some(selected_id) = self
+            .archive_list_state
+            .selected()
+            .and_then(|selected| self.archived_tasks().get(selected).map(|task| task.id))
+        else {
+            return Ok(());
+        };
+
+        self.update_task(selected_id, |task| task.archived = false)?;
+        self.clamp_archive_selection();
+
+        if let Some(task) = self.tasks.iter().find(|task| task.id == selected_id) {
+            self.toast = Some(Toast::info(format!("Restored {}", task.project)));
+        }
+
+        Ok(())
+    }
+
+    /// The task shown in the details view or one of its popups.
+    pub fn detail_task(&self) -> Option<&Task> {
+        let task_id = match &self.state {
+            State::TaskDetails { task_id }
+            | State::EditTimeFrame { task_id, .. }
+            | State::DeleteTimeFrame { task_id } => *task_id,
+            _ => return None,
+        };
+
+        self.tasks.iter().find(|task| task.id == task_id)
+    }
+
+    pub fn select_next_time_frame(&mut self) {
+        self.move_time_frame_selection(1);
+    }
+
+    pub fn select_previous_time_frame(&mut self) {
+        self.move_time_frame_selection(-1);
+    }
+
+    /// Rows of the details table of the task shown.
+    pub fn detail_rows(&self) -> Vec<DetailRow> {
+        self.detail_task()
+            .map(|task| detail_rows(task, self.config.time_zone()))
+            .unwrap_or_default()
+    }
+
+    /// Validates the time frame form and saves it. Invalid input is reported in the form
+    /// instead of failing.
+    pub fn save_time_frame(&mut self) -> Result<(), Error> {
+        let State::EditTimeFrame { task_id, form } = &mut self.state else {
+            return Ok(());
+        };
+        let task_id = *task_id;
+
+        let time_frame = match form.parse(self.config.time_zone()) {
+            Ok(time_frame) => time_frame,
+            Err(e) => {
+                form.error = Some(e.to_string());
+                return Ok(());
+            }
+        };
+
+        let is_new = form.time_frame_id.is_none();
+        self.update_task(task_id, |task| {
+            if is_new {
+                task.add_time_frame(time_frame);
+            } else {
+                // Deleted by another process in the meantime otherwise
+                let _ = task.edit_time_frame(time_frame);
+            }
+        })?;
+        self.state = State::TaskDetails { task_id };
+
+        Ok(())
+    }
+
+    /// Describes the recorded time frames the form's input overlaps, if it is valid.
+    pub fn time_frame_form_warning(&self) -> Option<String> {
+        let State::EditTimeFrame { form, .. } = &self.state else {
+            return None;
+        };
+        let time_zone = self.config.time_zone();
+        let time_frame = form.parse(time_zone).ok()?;
+
+        let overlaps: Vec<_> = find_overlaps(&self.tasks, &time_frame)
+            .into_iter()
+            .map(|(task, other)| {
+                format!(
+                    "{} {} - {}",
+                    task.project,
+                    format_timestamp(other.start_time, time_zone),
+                    format_timestamp(other.end_time, time_zone)
+                )
+            })
+            .collect();
+
+        (!overlaps.is_empty()).then(|| format!("Overlaps with {}", overlaps.join(", ")))
+    }
+
+    /// Saves the note entered in the note popup, removing the note if it was cleared.
+    pub fn save_note(&mut self) -> Result<(), Error> {
+        let State::EditNote {
+            task_id,
+            time_frame_id,
+            input,
+            in_details,
+        } = &self.state
+        else {
+            return Ok(());
+        };
+        let (task_id, time_frame_id, in_details) = (*task_id, *time_frame_id, *in_details);
+        let note = input.clone();
+
+        self.update_task(task_id, |task| {
+            // Deleted by another process in the meantime otherwise
+            let _ = task.set_note(time_frame_id, &note);
+        })?;
+        self.state = if in_details {
+            State::TaskDetails { task_id }
+        } else {
+            State::Projects
+        };
+
+        Ok(())
+    }
+
+    /// Time frames whose note matches the query of the search view.
+    pub fn search_results(&self) -> Vec<(&Task, &TimeFrame)> {
+        match &self.state {
+            State::SearchNotes { query } => search_notes(&self.tasks, query),
+            _ => vec![],
+        }
+    }
+
+    pub fn select_next_search_result(&mut self) {
+        let count = self.search_results().len();
+        move_selection(&mut self.search_list_state, count, 1);
+    }
+
+    pub fn select_previous_search_result(&mut self) {
+        let count = self.search_results().len();
+        move_selection(&mut self.search_list_state, count, -1);
+    }
+
+    /// Shows the time frame selected in the search view within the details of its project.
+    pub fn show_selected_search_result(&mut self) {
+        let Some((task_id, time_frame_id)) =
+            self.search_list_state.selected().and_then(|selected| {
+                self.search_results()
+                    .get(selected)
+                    .map(|(task, time_frame)| (task.id, time_frame.id))
+            })
+        else {
+            return;
+        };
+
+        self.state = State::TaskDetails { task_id };
+        let row = self.detail_task().and_then(|task| {
+            let index = task
+                .times
+                .iter()
+                .position(|time_frame| time_frame.id == time_frame_id)?;
+            self.detail_rows()
+                .iter()
+                .position(|row| matches!(row, DetailRow::TimeFrame(other) if *other == index))
+        });
+        self.time_frame_list_state.select(row);
+    }
+
+    pub fn delete_selected_time_frame(&mut self) -> Result<(), Error> {
+        let State::DeleteTimeFrame { task_id } = self.state else {
+            return Ok(());
+        };
+        let Some(time_frame_id) = self.selected_time_frame().map(|time_frame| time_frame.id) else {
+            return Ok(());
+        };
+
+        self.update_task(task_id, |task| {
+            let _ = task.delete_time_frame(time_frame_id);
+        })?;
+        self.state = State::TaskDetails { task_id };
+        self.clamp_time_frame_selection();
+
+        Ok(())
+    }
+
+    /// Moves the time frame form to the next or previous subtask of the project.
+    pub fn move_form_subtask(&mut self, step: isize) {
+        let subtasks: Vec<_> = self
+            .detail_task()
+            .map(|task| task.subtasks.iter().map(|subtask| subtask.id).collect())
+            .unwrap_or_default();
+        let State::EditTimeFrame { form, .. } = &mut self.state else {
+            return;
+        };
+
+        if let Some(index) = subtasks.iter().position(|id| *id == form.subtask_id) {
+            let next = (index as isize + step).rem_euclid(subtasks.len() as isize);
+            form.subtask_id = subtasks[next as usize];
+        }
+    }
+
+    pub fn select_next_report_range(&mut self) {
+        if let State::Report { form } = &mut self.state {
+            form.selected = (form.selected + 1) % REPORT_RANGES.len();
+            form.error = None;
+        }
+    }
+
+    pub fn select_previous_report_range(&mut self) {
+        if let State::Report { form } = &mut self.state {
+            form.selected = (form.selected + REPORT_RANGES.len() - 1) % REPORT_RANGES.len();
+            form.error = None;
+        }
+    }
+
+    /// Writes the report for the period chosen in the report popup. An invalid custom range is
+    /// reported in the form, the outcome of writing in a toast.
+    pub fn generate_report(&mut self) {
+        let State::Report { form } = &mut self.state else {
+            return;
+        };
+
+        let period = match form.period(self.config.time_zone()) {
+            Ok(period) => period,
+            Err(e) => {
+                form.error = Some(e.to_string());
+                return;
+            }
+        };
+
+        let report = Report::Summary(Summary::build(&self.tasks, &self.config, period));
+        self.toast = Some(
+            match archive_report(&report, self.config.report.format, &self.config.report) {
+                Ok(report_path) => {
+                    Toast::info(format!("Report written to {}", report_path.display()))
+                }
+                Err(e) => Toast::error(e.to_string()),
+            },
+        );
+        self.state = State::Projects;
+    }
+
+    /// The timesheet shown in the timesheet view.
+    pub fn timesheet(&self) -> Option<Timesheet> {
+        let State::Timesheet { period } = self.state else {
+            return None;
+        };
+
+        Some(Timesheet::build(&self.tasks, &self.config, period))
+    }
+
+    pub fn next_timesheet_period(&mut self) {
+        if let State::Timesheet { period } = &mut self.state {
+            *period = period.next();
+        }
+    }
+
+    pub fn previous_timesheet_period(&mut self) {
+        if let State::Timesheet { period } = &mut self.state {
+            *period = period.previous();
+        }
+    }
+
+    /// Switches the timesheet between the month and the week its first day falls in.
+    pub fn toggle_timesheet_span(&mut self) {
+        if let State::Timesheet { period } = &mut self.state {
+            *period = if period.is_month() {
+                Period::week(period.first_day)
+            } else {
+                Period::month(period.first_day)
+            };
+            self.timesheet_list_state.select(Some(0));
+        }
+    }
+
+    /// Moves the selected row of the timesheet view, whose last row holds the totals.
+    pub fn select_next_timesheet_day(&mut self) {
+        if let State::Timesheet { period } = self.state {
+            let rows = period.days().count() + 1;
+            let selected = self.timesheet_list_state.selected().unwrap_or(0);
+            self.timesheet_list_state
+                .select(Some((selected + 1) % rows));
+        }
+    }
+
+    pub fn select_previous_timesheet_day(&mut self) {
+        if let State::Timesheet { period } = self.state {
+            let rows = period.days().count() + 1;
+            let selected = self.timesheet_list_state.selected().unwrap_or(0);
+            self.timesheet_list_state
+                .select(Some((selected + rows - 1) % rows));
+        }
+    }
+
+    /// Writes the timesheet shown to the report directory, reporting the outcome in a toast.
+    pub fn export_timesheet(&mut self) {
+        let Some(timesheet) = self.timesheet() else {
+            return;
+        };
+
+        let report = Report::Timesheet(timesheet);
+        self.toast = Some(
+            match archive_report(&report, self.config.report.format, &self.config.report) {
+                Ok(report_path) => {
+                    Toast::info(format!("Timesheet written to {}", report_path.display()))
+                }
+                Err(e) => Toast::error(e.to_string()),
+            },
+        );
+    }
+
+    /// Notes a key press for idle detection. Returns `false` if it ended a time away from a
+    /// running timer, in which case the idle popup is shown instead of handling the key.
+    pub fn register_input(&mut self) -> bool {
+        let timer_running = self.tasks.iter().any(Task::is_running);
+        match self.idle.input(Utc::now(), timer_running) {
+            Some(away) => !self.show_idle(away),
+            None => true,
+        }
+    }
+
+    /// Runs the idle command when due, showing the idle popup if it reports that the user came
+    /// back.
+    pub fn check_idle(&mut self) {
+        if matches!(self.state, State::Idle { .. }) {
+            return;
+        }
+
+        let timer_running = self.tasks.iter().any(Task::is_running);
+        match self.idle.tick(Utc::now(), timer_running) {
+            Ok(Some(away)) => {
+                self.show_idle(away);
+            }
+            Ok(None) => {}
+            Err(e) => self.show_error(&e),
+        }
+    }
+
+    /// Projects whose timer was already running when the user went away at `since`.
+    pub fn idle_tasks(&self, since: DateTime<Utc>) -> Vec<&Task> {
+        self.tasks
+            .iter()
+            .filter(|task| task.running_since.is_some_and(|start| start < since))
+            .collect()
+    }
+
+    fn show_idle(&mut self, away: Away) -> bool {
+        if matches!(self.state, State::Idle { .. }) || self.idle_tasks(away.since).is_empty() {
+            return false;
+        }
+
+        let previous = std::mem::replace(&mut self.state, State::Projects);
+        self.state = State::Idle {
+            since: away.since,
+            until: away.until,
+            previous: Box::new(previous),
+        };
+
+        true
+    }
+
+    /// Applies the choice made in the idle popup to every timer that ran while the user was
+    /// away, and returns to where they left off.
+    pub fn resolve_idle(&mut self, choice: IdleChoice) -> Result<(), Error> {
+        let State::Idle { since, until, .. } = self.state else {
+            return Ok(());
+        };
+
+        if choice != IdleChoice::Keep {
+            let merge_gap = self.config.merge_gap_on_stop();
+            let mut operation = None;
+            self.update(|tasks| {
+                let before = tasks.clone();
+                for task in tasks
+                    .iter_mut()
+                    .filter(|task| task.running_since.is_some_and(|start| start < since))
+                {
+                    task.remove_idle_time(since, until, choice == IdleChoice::Split, merge_gap);
+                }
+                operation = Operation::toggle_timer(&before, tasks);
+            })?;
+            self.record(operation);
+
+            let action = match choice {
+                IdleChoice::Split => "Split off",
+                _ => "Discarded",
+            };
+            self.toast = Some(Toast::info(format!(
+                "{} {} of idle time",
+                action,
+                format_duration(until - since)
+            )));
+        }
+
+        if let State::Idle { previous, .. } = std::mem::replace(&mut self.state, State::Projects) {
+            self.state = *previous;
+        }
+
+        Ok(())
+    }
+
+    /// Saves the last input with the running timers every few minutes, so that a timer left
+    /// running can later be stopped at that time.
+    pub fn save_activity(&mut self) -> Result<(), Error> {
+        // The timers asked about keep the last activity from before the start
+        if matches!(self.state, State::LongRunningTimer { .. }) {
+            return Ok(());
+        }
+
+        let last_activity = self.idle.last_activity();
+        let is_due = self.tasks.iter().any(|task| {
+            task.last_activity().is_some_and(|saved| {
+                last_activity - saved >= Duration::minutes(ACTIVITY_SAVE_MINUTES)
+            })
+        });
+
+        if is_due {
+            self.update(|tasks| {
+                for task in tasks.iter_mut().filter(|task| task.is_running()) {
+                    task.last_active = Some(last_activity);
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Asks about the next timer found running for too long on startup, or returns to the
+    /// projects table once all were handled.
+    fn show_next_long_running(&mut self) {
+        let time_zone = self.config.time_zone();
+        while let Some(task_id) = self.long_running.pop() {
+            let last_activity = self
+                .tasks
+                .iter()
+                .find(|task| task.id == task_id)
+                .and_then(Task::last_activity);
+            if let Some(last_activity) = last_activity {
+                self.state = State::LongRunningTimer {
+                    task_id,
+                    form: StopTimerForm {
+                        selected: 0,
+                        stop_at: format_timestamp(last_activity, time_zone),
+                        error: None,
+                    },
+                };
+                return;
+            }
+        }
+
+        self.state = State::Projects;
+    }
+
+    pub fn select_next_stop_choice(&mut self) {
+        if let State::LongRunningTimer { form, .. } = &mut self.state {
+            form.selected = (form.selected + 1) % STOP_CHOICES.len();
+            form.error = None;
+        }
+    }
+
+    pub fn select_previous_stop_choice(&mut self) {
+        if let State::LongRunningTimer { form, .. } = &mut self.state {
+            form.selected = (form.selected + STOP_CHOICES.len() - 1) % STOP_CHOICES.len();
+            form.error = None;
+        }
+    }
+
+    /// Stops the timer of the long running timer popup as chosen, or keeps it running. An
+    /// invalid stop time is reported in the popup.
+    pub fn resolve_long_running(&mut self) -> Result<(), Error> {
+        let State::LongRunningTimer { task_id, form } = &mut self.state else {
+            return Ok(());
+        };
+        let task_id = *task_id;
+        let Some(task) = self.tasks.iter().find(|task| task.id == task_id) else {
+            self.show_next_long_running();
+            return Ok(());
+        };
+
+        let end_time = match form.choice() {
+            StopChoice::LastActivity => Some(task.last_activity_stop_time()),
+            StopChoice::ChosenTime => Some(parse_timestamp(&form.stop_at, self.config.time_zone())),
+            StopChoice::Keep => None,
+        };
+        let end_time = match end_time.map(|end_time| {
+            end_time.and_then(|end_time| task.check_stop_time(end_time).map(|()| end_time))
+        }) {
+            Some(Ok(end_time)) => Some(end_time),
+            Some(Err(e)) => {
+                form.error = Some(e.to_string());
+                return Ok(());
+            }
+            None => None,
+        };
+
+        if let Some(end_time) = end_time {
+            let merge_gap = self.config.merge_gap_on_stop();
+            let mut operation = None;
+            self.update(|tasks| {
+                let before = tasks.clone();
+                if let Some(task) = tasks.iter_mut().find(|task| task.id == task_id) {
+                    task.stop_at(end_time, merge_gap);
+                }
+                operation = Operation::toggle_timer(&before, tasks);
+            })?;
+            self.record(operation);
+        }
+        self.show_next_long_running();
+
+        Ok(())
+    }
+
+    /// Shows an error that should not end the TUI, such as the DB being locked for too long.
+    pub fn show_error(&mut self, error: &Error) {
+        self.toast = Some(Toast::error(error.to_string()));
+    }
+
+    /// Hides the toast once it has been shown for `TOAST_DURATION`.
+    pub fn expire_toast(&mut self) {
+        if self
+            .toast
+            .as_ref()
+            .is_some_and(|toast| toast.shown_at.elapsed() >= TOAST_DURATION)
+        {
+            self.toast = None;
+        }
+    }
+
+    fn selected_time_frame(&self) -> Option<&TimeFrame> {
+        let selected = self.time_frame_list_state.selected()?;
+        let DetailRow::TimeFrame(index) = *self.detail_rows().get(selected)? else {
+            return None;
+        };
+
+        self.detail_task()?.times.get(index)
+    }
+
+    /// Moves the selection by `step` rows, skipping day totals and wrapping around.
+    fn move_time_frame_selection(&mut self, step: isize) {
+        let rows = self.detail_rows();
+        if !rows.iter().any(DetailRow::is_time_frame) {
+            return;
+        }
+
+        let mut selected = self.time_frame_list_state.selected().unwrap_or(0) as isize;
+        loop {
+            selected = (selected + step).rem_euclid(rows.len() as isize);
+            if rows[selected as usize].is_time_frame() {
+                break;
+            }
+        }
+        self.time_frame_list_state.select(Some(selected as usize));
+    }
+
+    /// Selects the most recent time frame, which is shown last.
+    fn select_last_time_frame(&mut self) {
+        let last = self
+            .detail_rows()
+            .iter()
+            .rposition(DetailRow::is_time_frame);
+        self.time_frame_list_state.select(last);
+    }
+
+    fn clamp_time_frame_selection(&mut self) {
+        let rows = self.detail_rows();
+        let selected = self.time_frame_list_state.selected();
+
+        if !selected
+            .is_some_and(|selected| rows.get(selected).is_some_and(DetailRow::is_time_frame))
+        {
+            self.select_last_time_frame();
+        }
+    }
+
+    fn selected_row(&self) -> Option<ProjectRow<'_>> {
+        let selected = self.task_list_state.selected()?;
+        self.project_rows().into_iter().nth(selected)
+    }
+
+    /// The project of the selected row, which may be one of its subtasks.
+    fn selected_task(&self) -> Option<&Task> {
+        match self.selected_row()? {
+            ProjectRow::Project { task, .. } | ProjectRow::Subtask(task, _) => Some(task),
+        }
+    }
+
+    fn selected_subtask_id(&self) -> Option<Uuid> {
+        match self.selected_row()? {
+            ProjectRow::Subtask(_, subtask) => Some(subtask.id),
+            ProjectRow::Project { .. } => None,
+        }
+    }
+
+    /// Selects the row of a project, e.g. after collapsing it from one of its subtasks.
+    fn select_project(&mut self, task_id: Uuid) {
+        let row = self
+            .project_rows()
+            .iter()
+            .position(|row| matches!(row, ProjectRow::Project { task, .. } if task.id == task_id));
+        if row.is_some() {
+            self.task_list_state.select(row);
+        }
+    }
+
+    /// The selected task is tracked by ID when mutating, as other processes may have reordered
+    /// the task list since it was loaded.
+    fn selected_id(&self) -> Option<Uuid> {
+        self.selected_task().map(|task| task.id)
+    }
+
+    /// Applies a mutation to the persisted task list and replaces the in-memory model with the
+    /// result, so changes made by other processes in the meantime are not lost.
+    fn update(&mut self, updater: impl FnOnce(&mut Vec<Task>)) -> Result<(), Error> {
+        self.update_data(|data| updater(&mut data.tasks))
+    }
+
+    fn update_data(&mut self, updater: impl FnOnce(&mut Data)) -> Result<(), Error> {
+        let data = self.repository.update(updater)?;
+        self.set_data(data);
+
+        Ok(())
+    }
+
+    fn set_data(&mut self, data: Data) {
+        self.tasks = data.tasks;
+        self.trash = data.trash;
+    }
+
+    fn selected_trashed(&self) -> Option<&TrashedTask> {
+        self.trash.get(self.trash_list_state.selected()?)
+    }
+
+    /// Applies a change to a single task and records it for undo, unless the task is gone or
+    /// nothing changed.
+    fn update_task(&mut self, task_id: Uuid, updater: impl FnOnce(&mut Task)) -> Result<(), Error> {
+        let mut operation = None;
+        self.update(|tasks| {
+            if let Some(task) = tasks.iter_mut().find(|task| task.id == task_id) {
+                let before = task.clone();
+                updater(task);
+                if *task != before {
+                    operation = Some(Operation::EditProject {
+                        before,
+                        after: task.clone(),
+                    });
+                }
+            }
+        })?;
+        self.record(operation);
+
+        Ok(())
+    }
+
+    fn record(&mut self, operation: Option<Operation>) {
+        if let Some(operation) = operation {
+            self.history.record(operation);
+        }
+    }
+
+    fn clamp_selection(&mut self) {
+        let count = self.project_rows().len();
+        let selected = self.task_list_state.selected().unwrap_or(0);
+        self.task_list_state
+            .select(Some(selected.min(count.saturating_sub(1))));
+    }
+
+    fn clamp_trash_selection(&mut self) {
+        let selected = self.trash_list_state.selected().unwrap_or(0);
+        self.trash_list_state
+            .select(Some(selected.min(self.trash.len().saturating_sub(1))));
+    }
+
+    fn clamp_archive_selection(&mut self) {
+        let count = self.archived_tasks().len();
+        let selected = self.archive_list_state.selected().unwrap_or(0);
+        self.archive_list_state
+            .select(Some(selected.min(count.saturating_sub(1))));
+    }
+}
+
+/// Moves the selection of a table with `count` rows by `step`, wrapping around.
+fn move_selection(state: &mut TableState, count: usize, step: isize) {
+    if count == 0 {
+        return;
+    }
+
+    let selected = match state.selected() {
+        Some(selected) => (selected as isize + step).rem_euclid(count as isize) as usize,
+        None => 0,
+    };
+    state.select(Some(selected));
+}
+
+/// Row of the task details table.
+pub enum DetailRow {
+    /// Index of the time frame in the task's `times`
+    TimeFrame(usize),
+    DayTotal {
+        day: NaiveDate,
+        total: Duration,
+    },
+}
+
+impl DetailRow {
+    fn is_time_frame(&self) -> bool {
+        matches!(self, DetailRow::TimeFrame(_))
+    }
+}
+
+/// Lists the time frames of a task in order, followed by a subtotal after the last time frame
+/// of each day. Time frames are assigned to the day they start on in `time_zone`.
+pub fn detail_rows(task: &Task, time_zone: Tz) -> Vec<DetailRow> {
+    let mut rows = vec![];
+    let mut day_total = Duration::zero();
+
+    for (index, time_frame) in task.times.iter().enumerate() {
+        rows.push(DetailRow::TimeFrame(index));
+        day_total = day_total + time_frame.duration();
+
+        let day = local_day(time_frame.start_time, time_zone);
+        let next_day = task
+            .times
+            .get(index + 1)
+            .map(|next| local_day(next.start_time, time_zone));
+        if next_day != Some(day) {
+            rows.push(DetailRow::DayTotal {
+                day,
+                total: day_total,
+            });
+            day_total = Duration::zero();
+        }
+    }
+
+    rows
+}
+
+/// Splits comma separated tags, dropping empty and repeated ones.
+fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in input.split(',').map(str::trim) {
+        if !tag.is_empty() && !tags.iter().any(|other| other == tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+
+    tags
+}
+
+impl ProjectForm {
+    fn focused_input(&mut self) -> &mut String {
+        match self.focus {
+            ProjectField::Name => &mut self.name,
+            ProjectField::Client => &mut self.client,
+            ProjectField::Tags => &mut self.tags,
+        }
+    }
+}
+
+impl TimeFrameForm {
+    fn focused_input(&mut self) -> &mut String {
+        match self.focus {
+            FormField::Start => &mut self.start,
+            FormField::End => &mut self.end,
+        }
+    }
+
+    fn parse(&self, time_zone: Tz) -> Result<TimeFrame, Error> {
+        let mut time_frame = TimeFrame::new(
+            self.subtask_id,
+            parse_timestamp(&self.start, time_zone)?,
+            parse_timestamp(&self.end, time_zone)?,
+        )?;
+        if let Some(id) = self.time_frame_id {
+            time_frame.id = id;
+        }
+
+        Ok(time_frame)
+    }
+}
+
+impl Toast {
+    fn info(message: String) -> Self {
+        Toast {
+            message,
+            is_error: false,
+            shown_at: Instant::now(),
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Toast {
+            message,
+            is_error: true,
+            shown_at: Instant::now(),
+        }
+    }
+}
+
+impl StopTimerForm {
+    pub fn choice(&self) -> StopChoice {
+        STOP_CHOICES[self.selected]
+    }
+}
+
+impl StopChoice {
+    pub fn label(self) -> &'static str {
+        match self {
+            StopChoice::LastActivity => "Stop at last activity",
+            StopChoice::ChosenTime => "Stop at",
+            StopChoice::Keep => "Keep running",
+        }
+    }
+}
+
+impl ReportForm {
+    pub fn range(&self) -> ReportRange {
+        REPORT_RANGES[self.selected]
+    }
+
+    fn focused_input(&mut self) -> &mut String {
+        match self.focus {
+            FormField::Start => &mut self.from,
+            FormField::End => &mut self.to,
+        }
+    }
+
+    /// The chosen period, or `None` to report all recorded time.
+    fn period(&self, time_zone: Tz) -> Result<Option<Period>, Error> {
+        Ok(match self.range() {
+            ReportRange::AllTime => None,
+            ReportRange::Preset(preset) => Some(preset.period(today(time_zone))),
+            ReportRange::Custom => {
+                Some(Period::new(parse_date(&self.from)?, parse_date(&self.to)?)?)
+            }
+        })
+    }
+}
+
+impl ReportRange {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReportRange::AllTime => "All time",
+            ReportRange::Preset(preset) => preset.label(),
+            ReportRange::Custom => "Custom",
+        }
+    }
+}
@@ -0,0 +1,710 @@
+use std::path::PathBuf;
+
+use chrono::{Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand};
+use uuid::Uuid;
+
+use crate::{
+    config::{Config, MergePolicy},
+    details::Details,
+    doctor::long_running_timers,
+    find_overlaps, format_duration, format_timestamp, group_adjacent, merge_group, parse_timestamp,
+    period::{parse_date, parse_month, Period, Preset},
+    report::{archive_report, write_report, Grouping, Report, ReportFormat, Summary, STDOUT_PATH},
+    rounding::{Increment, RoundingMode, RoundingScope},
+    search_notes,
+    storage::{self, Storage, StorageKind},
+    time_zone::today,
+    timesheet::Timesheet,
+    toggle_timer, validate_project_name, Error, Task, TimeFrame,
+};
+
+#[derive(Parser)]
+#[command(about = "Track the time spent on projects", version)]
+pub struct Cli {
+    /// Path of the DB file, overriding TIME_TRACKING_DB and the config file
+    #[arg(long, global = true)]
+    pub db: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the timer of a project, stopping any other running timer
+    Start {
+        project: String,
+        /// Task of the project to track, by default the one tracked last
+        task: Option<String>,
+    },
+    /// Stop all running timers
+    Stop,
+    /// Show the running timers
+    Status,
+    /// List all projects with their status and total time
+    List {
+        /// List the archived projects instead
+        #[arg(long)]
+        archived: bool,
+        /// Only list projects with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// List the tasks of each project below it
+        #[arg(long)]
+        tasks: bool,
+    },
+    /// Create a new project, or add a task to an existing one
+    Add {
+        project: String,
+        /// Name of the task, by default "General" for a new project
+        task: Option<String>,
+    },
+    /// Generate a report of the time spent per project
+    Report {
+        /// Rounding direction, overriding the [rounding] section of the config file
+        #[arg(long, value_enum)]
+        round: Option<RoundingMode>,
+        /// Rounding increment in minutes: 1, 6, 15 or 30
+        #[arg(long)]
+        increment: Option<Increment>,
+        /// Whether to round every time frame, the time per day or the project's total
+        #[arg(long, value_enum)]
+        per: Option<RoundingScope>,
+        /// Only report time within a period relative to today
+        #[arg(long, value_enum, conflicts_with_all = ["from", "month"])]
+        period: Option<Preset>,
+        /// First day to report, as YYYY-MM-DD
+        #[arg(long, value_parser = parse_date, requires = "to", conflicts_with = "month")]
+        from: Option<NaiveDate>,
+        /// Last day to report, as YYYY-MM-DD
+        #[arg(long, value_parser = parse_date, requires = "from")]
+        to: Option<NaiveDate>,
+        /// Only report time within a calendar month, as YYYY-MM
+        #[arg(long, value_parser = parse_month)]
+        month: Option<Period>,
+        /// Break the time down per project and day, for the current week unless a period is
+        /// given
+        #[arg(long)]
+        timesheet: bool,
+        /// List every time frame with its note instead of adding up the time
+        #[arg(long, conflicts_with = "timesheet")]
+        details: bool,
+        /// Add up the time per project, client or tag, overriding the [report] section of the
+        /// config file
+        #[arg(long, value_enum, conflicts_with_all = ["timesheet", "details"])]
+        by: Option<Grouping>,
+        /// Output format, overriding the [report] section of the config file
+        #[arg(long, value_enum)]
+        format: Option<ReportFormat>,
+        /// File to write the report to instead of the report directory, or - for standard
+        /// output
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// List, add, edit or delete the recorded time frames of a project
+    Time {
+        #[command(subcommand)]
+        command: TimeCommand,
+    },
+    /// Merge time frames of a project that are close to each other
+    Compact {
+        /// Largest gap between merged time frames in minutes, defaulting to the one configured
+        /// in the [merge] section
+        #[arg(long)]
+        gap: Option<i64>,
+        /// Only show which time frames would be merged
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check for timers left running for longer than configured in the [long_running] section
+    Doctor {
+        /// Stop them at the last input the TUI noticed while they were running
+        #[arg(long, conflicts_with = "stop_at")]
+        stop_at_last_activity: bool,
+        /// Stop them at this time, as YYYY-MM-DD HH:MM in the configured time zone
+        #[arg(long)]
+        stop_at: Option<String>,
+    },
+    /// Copy all data from the configured storage into a new, empty storage
+    MigrateStorage {
+        /// Kind of the target storage
+        #[arg(value_enum)]
+        to: StorageKind,
+        /// Location of the target storage
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TimeCommand {
+    /// List the recorded time frames of a project
+    List { project: String },
+    /// Record a time frame for a project
+    Add {
+        project: String,
+        /// Start time, as YYYY-MM-DD HH:MM in the configured time zone
+        #[arg(long)]
+        start: String,
+        /// End time, as YYYY-MM-DD HH:MM in the configured time zone
+        #[arg(long)]
+        end: String,
+        /// Task of the project the time was spent on, by default the one tracked last
+        #[arg(long)]
+        task: Option<String>,
+        /// What was done
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Change the start or end time, the task or the note of a time frame
+    Edit {
+        /// ID of the time frame, or a unique prefix of it
+        id: String,
+        /// New start time, as YYYY-MM-DD HH:MM in the configured time zone
+        #[arg(long)]
+        start: Option<String>,
+        /// New end time, as YYYY-MM-DD HH:MM in the configured time zone
+        #[arg(long)]
+        end: Option<String>,
+        /// Task of the same project to move the time frame to
+        #[arg(long)]
+        task: Option<String>,
+        /// New note, or an empty one to remove it
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Delete a time frame
+    Delete {
+        /// ID of the time frame, or a unique prefix of it
+        id: String,
+    },
+    /// List the time frames of all projects whose note contains a text, ignoring case
+    Search { query: String },
+}
+
+impl Command {
+    /// Whether the command may change the data, which is when expired projects are purged from
+    /// the trash. Commands that only read never write the storage.
+    pub fn writes(&self) -> bool {
+        match self {
+            Command::Start { .. } | Command::Stop | Command::Add { .. } => true,
+            Command::Status
+            | Command::List { .. }
+            | Command::Report { .. }
+            | Command::MigrateStorage { .. } => false,
+            Command::Time { command } => !matches!(
+                command,
+                TimeCommand::List { .. } | TimeCommand::Search { .. }
+            ),
+            Command::Compact { dry_run, .. } => !dry_run,
+            Command::Doctor {
+                stop_at_last_activity,
+                stop_at,
+            } => *stop_at_last_activity || stop_at.is_some(),
+        }
+    }
+}
+
+pub fn run(
+    command: Command,
+    storage: Box<dyn Storage>,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Start { project, task } => {
+            let started = storage.update_db(|tasks| {
+                let selected = find_project(tasks, &project)?;
+                let subtask_id = match &task {
+                    Some(name) => tasks[selected].find_subtask(name)?.id,
+                    None => tasks[selected].current_subtask(),
+                };
+                if !tasks[selected].is_running_subtask(subtask_id) {
+                    toggle_timer(tasks, selected, subtask_id, config.merge_gap_on_stop());
+                }
+
+                Ok::<_, Error>(subtask_label(&tasks[selected], subtask_id))
+            })??;
+
+            println!("Started {}", started);
+        }
+        Command::Stop => {
+            let stopped = storage.update_db(|tasks| {
+                let mut stopped = vec![];
+                for task in tasks.iter_mut().filter(|task| task.is_running()) {
+                    stopped.push((
+                        subtask_label(task, task.current_subtask()),
+                        task.current_duration(),
+                    ));
+                    task.stop(config.merge_gap_on_stop());
+                }
+
+                stopped
+            })?;
+
+            if stopped.is_empty() {
+                println!("No timer running");
+            }
+            for (project, duration) in stopped {
+                println!("Stopped {} after {}", project, format_duration(duration));
+            }
+        }
+        Command::Status => {
+            let tasks = storage.read_db()?;
+            let mut running = tasks.iter().filter(|task| task.is_running()).peekable();
+
+            if running.peek().is_none() {
+                println!("No timer running");
+            }
+            for task in running {
+                println!(
+                    "{} running for {}",
+                    subtask_label(task, task.current_subtask()),
+                    format_duration(task.current_duration())
+                );
+            }
+        }
+        Command::List {
+            archived,
+            tag,
+            tasks: list_subtasks,
+        } => {
+            let tasks = storage.read_db()?;
+            for task in tasks.iter().filter(|task| {
+                task.archived == archived && tag.as_ref().is_none_or(|tag| task.tags.contains(tag))
+            }) {
+                println!(
+                    "{}\t{}\t{}",
+                    task.project,
+                    if task.is_running() {
+                        "Running"
+                    } else {
+                        "Not running"
+                    },
+                    format_duration(task.total_duration())
+                );
+
+                if list_subtasks {
+                    for subtask in &task.subtasks {
+                        println!(
+                            "  {}\t{}\t{}",
+                            subtask.name,
+                            if task.is_running_subtask(subtask.id) {
+                                "Running"
+                            } else {
+                                "Not running"
+                            },
+                            format_duration(task.subtask_duration(subtask.id))
+                        );
+                    }
+                }
+            }
+        }
+        Command::Add { project, task } => {
+            let task = task.map(|name| name.trim().to_owned());
+            if task.as_ref().is_some_and(String::is_empty) {
+                return Err(Error::EmptySubtaskName.into());
+            }
+
+            let project = project.trim().to_owned();
+            storage.update_db(|tasks| {
+                match (
+                    tasks.iter_mut().find(|other| other.project == project),
+                    &task,
+                ) {
+                    (Some(existing), Some(name)) => {
+                        existing.add_subtask(name.clone())?;
+                    }
+                    (_, name) => {
+                        let mut new = Task::new(validate_project_name(tasks, &project, None)?);
+                        if let Some(name) = name {
+                            new.subtasks[0].name = name.clone();
+                        }
+                        tasks.push(new);
+                    }
+                }
+
+                Ok::<_, Error>(())
+            })??;
+
+            match task {
+                Some(name) => println!("Added {} / {}", project, name),
+                None => println!("Added {}", project),
+            }
+        }
+        Command::Report {
+            round,
+            increment,
+            per,
+            period,
+            from,
+            to,
+            month,
+            timesheet,
+            details,
+            by,
+            format,
+            output,
+        } => {
+            let mut config = config.clone();
+            let rounding = &mut config.rounding;
+            rounding.mode = round.unwrap_or(rounding.mode);
+            rounding.increment = increment.unwrap_or(rounding.increment);
+            rounding.scope = per.unwrap_or(rounding.scope);
+            config.report.group_by = by.unwrap_or(config.report.group_by);
+
+            let period = match (period, from.zip(to), month) {
+                (Some(preset), _, _) => Some(preset.period(today(config.time_zone()))),
+                (_, Some((from, to)), _) => Some(Period::new(from, to)?),
+                (_, _, month) => month,
+            };
+
+            let format = format.unwrap_or(config.report.format);
+            let tasks = storage.read_db()?;
+            let report = if timesheet {
+                let period =
+                    period.unwrap_or_else(|| Preset::ThisWeek.period(today(config.time_zone())));
+                Report::Timesheet(Timesheet::build(&tasks, &config, period))
+            } else if details {
+                Report::Details(Details::build(&tasks, &config, period))
+            } else {
+                Report::Summary(Summary::build(&tasks, &config, period))
+            };
+
+            match output {
+                Some(path) if path.as_os_str() == STDOUT_PATH => {
+                    write_report(&report, format, &path)?;
+                }
+                Some(path) => {
+                    write_report(&report, format, &path)?;
+                    println!("{}", path.display());
+                }
+                None => {
+                    let report_path = archive_report(&report, format, &config.report)?;
+                    println!("{}", report_path.display());
+                }
+            }
+        }
+        Command::Time { command } => run_time(command, storage.as_ref(), config.time_zone())?,
+        Command::Compact { gap, dry_run } => {
+            let gap = match gap {
+                Some(gap) => Duration::minutes(gap),
+                None => config
+                    .merge
+                    .as_ref()
+                    .map_or_else(|| MergePolicy::default().gap(), MergePolicy::gap),
+            };
+
+            if dry_run {
+                let merged = print_merges(&storage.read_db()?, gap, config.time_zone());
+                println!("Would merge {} time frames", merged);
+            } else {
+                let merged = storage.update_db(|tasks| {
+                    let merged = print_merges(tasks, gap, config.time_zone());
+                    for task in tasks.iter_mut() {
+                        task.times = task.merged_times(gap);
+                    }
+
+                    merged
+                })?;
+                println!("Merged {} time frames", merged);
+            }
+        }
+        Command::Doctor {
+            stop_at_last_activity,
+            stop_at,
+        } => {
+            let time_zone = config.time_zone();
+            let stop_at = stop_at
+                .map(|input| parse_timestamp(&input, time_zone))
+                .transpose()?;
+            let merge_gap = config.merge_gap_on_stop();
+
+            let find = |tasks: &[Task]| {
+                let mut found = vec![];
+                for task in long_running_timers(tasks, config, Utc::now()) {
+                    let running_since = task.running_since.expect("the timer is running");
+                    let end_time = match stop_at {
+                        Some(stop_at) => Some(stop_at),
+                        None if stop_at_last_activity => Some(task.last_activity_stop_time()?),
+                        None => None,
+                    };
+                    if let Some(end_time) = end_time {
+                        task.check_stop_time(end_time)?;
+                    }
+
+                    let last_active = match task.recorded_activity() {
+                        Some(last_activity) => format_timestamp(last_activity, time_zone),
+                        None => "never".to_owned(),
+                    };
+                    let description = format!(
+                        "{} running since {} ({}), last active {}",
+                        subtask_label(task, task.current_subtask()),
+                        format_timestamp(running_since, time_zone),
+                        format_duration(task.current_duration()),
+                        last_active
+                    );
+                    found.push((task.id, description, end_time));
+                }
+
+                Ok::<_, Error>(found)
+            };
+
+            // Only checking for timers leaves the storage untouched
+            let found = if stop_at_last_activity || stop_at.is_some() {
+                storage.update_db(|tasks| {
+                    let found = find(tasks)?;
+
+                    // Only stopped once every stop time was checked, so that a failing one
+                    // leaves all timers running
+                    for (task_id, _, end_time) in &found {
+                        if let (Some(task), Some(end_time)) =
+                            (tasks.iter_mut().find(|task| task.id == *task_id), end_time)
+                        {
+                            task.stop_at(*end_time, merge_gap);
+                        }
+                    }
+
+                    Ok::<_, Error>(found)
+                })??
+            } else {
+                find(&storage.read_db()?)?
+            };
+
+            if found.is_empty() {
+                println!("No timer running for too long");
+            }
+            for (_, description, end_time) in &found {
+                println!("{}", description);
+                if let Some(end_time) = end_time {
+                    println!("  stopped at {}", format_timestamp(*end_time, time_zone));
+                }
+            }
+            if !found.is_empty() && !stop_at_last_activity && stop_at.is_none() {
+                println!("Stop them with --stop-at-last-activity or --stop-at <time>");
+            }
+        }
+        Command::MigrateStorage { to, path } => {
+            let target = storage::open(to, &path)?;
+            let migrated = storage::migrate(storage.as_ref(), target.as_ref())?;
+
+            println!(
+                "Copied {} projects from {} to {}",
+                migrated,
+                storage.path().display(),
+                path.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_time(command: TimeCommand, storage: &dyn Storage, time_zone: Tz) -> Result<(), Error> {
+    match command {
+        TimeCommand::List { project } => {
+            let tasks = storage.read_db()?;
+            let task = &tasks[find_project(&tasks, &project)?];
+
+            for time_frame in &task.times {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    short_id(time_frame.id),
+                    task.subtask(time_frame.subtask_id)
+                        .map_or("", |subtask| subtask.name.as_str()),
+                    format_timestamp(time_frame.start_time, time_zone),
+                    format_timestamp(time_frame.end_time, time_zone),
+                    format_duration(time_frame.duration()),
+                    time_frame.note.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        TimeCommand::Add {
+            project,
+            start,
+            end,
+            task,
+            note,
+        } => {
+            let (start, end) = (
+                parse_timestamp(&start, time_zone)?,
+                parse_timestamp(&end, time_zone)?,
+            );
+
+            let time_frame = storage.update_db(|tasks| {
+                let selected = find_project(tasks, &project)?;
+                let subtask_id = match &task {
+                    Some(name) => tasks[selected].find_subtask(name)?.id,
+                    None => tasks[selected].current_subtask(),
+                };
+                let time_frame = TimeFrame::new(subtask_id, start, end)?;
+                warn_overlaps(tasks, &time_frame, time_zone);
+                tasks[selected].add_time_frame(time_frame.clone());
+                if let Some(note) = &note {
+                    tasks[selected].set_note(time_frame.id, note)?;
+                }
+
+                Ok::<_, Error>(time_frame)
+            })??;
+
+            println!("Added {} to {}", short_id(time_frame.id), project);
+        }
+        TimeCommand::Edit {
+            id,
+            start,
+            end,
+            task,
+            note,
+        } => {
+            let parse = |input: Option<String>| {
+                input
+                    .map(|input| parse_timestamp(&input, time_zone))
+                    .transpose()
+            };
+            let (start, end) = (parse(start)?, parse(end)?);
+
+            let edited = storage.update_db(|tasks| {
+                let (selected, id) = find_time_frame(tasks, &id)?;
+                let current = tasks[selected]
+                    .times
+                    .iter()
+                    .find(|time_frame| time_frame.id == id)
+                    .expect("was found above");
+
+                let subtask_id = match &task {
+                    Some(name) => tasks[selected].find_subtask(name)?.id,
+                    None => current.subtask_id,
+                };
+
+                let mut edited = TimeFrame::new(
+                    subtask_id,
+                    start.unwrap_or(current.start_time),
+                    end.unwrap_or(current.end_time),
+                )?;
+                edited.id = id;
+
+                warn_overlaps(tasks, &edited, time_zone);
+                tasks[selected].edit_time_frame(edited)?;
+                if let Some(note) = &note {
+                    tasks[selected].set_note(id, note)?;
+                }
+
+                Ok::<_, Error>(id)
+            })??;
+
+            println!("Edited {}", short_id(edited));
+        }
+        TimeCommand::Delete { id } => {
+            let deleted = storage.update_db(|tasks| {
+                let (selected, id) = find_time_frame(tasks, &id)?;
+
+                tasks[selected].delete_time_frame(id)
+            })??;
+
+            println!("Deleted {}", short_id(deleted.id));
+        }
+        TimeCommand::Search { query } => {
+            let tasks = storage.read_db()?;
+
+            for (task, time_frame) in search_notes(&tasks, &query) {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    short_id(time_frame.id),
+                    task.project,
+                    format_timestamp(time_frame.start_time, time_zone),
+                    format_timestamp(time_frame.end_time, time_zone),
+                    format_duration(time_frame.duration()),
+                    time_frame.note.as_deref().unwrap_or_default()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the time frames that are at most `gap` apart and the time frame they would be merged
+/// into. Returns the number of time frames that would be merged into others.
+fn print_merges(tasks: &[Task], gap: Duration, time_zone: Tz) -> usize {
+    let mut merged = 0;
+
+    for task in tasks {
+        for group in group_adjacent(&task.times, gap) {
+            if group.len() < 2 {
+                continue;
+            }
+
+            let sources: Vec<_> = group
+                .iter()
+                .map(|time_frame| format_range(time_frame, time_zone))
+                .collect();
+            println!(
+                "{}: {} -> {}",
+                task.project,
+                sources.join(", "),
+                format_range(&merge_group(&group), time_zone)
+            );
+            merged += group.len() - 1;
+        }
+    }
+
+    merged
+}
+
+fn format_range(time_frame: &TimeFrame, time_zone: Tz) -> String {
+    format!(
+        "{} - {}",
+        format_timestamp(time_frame.start_time, time_zone),
+        format_timestamp(time_frame.end_time, time_zone)
+    )
+}
+
+fn warn_overlaps(tasks: &[Task], time_frame: &TimeFrame, time_zone: Tz) {
+    for (task, other) in find_overlaps(tasks, time_frame) {
+        eprintln!(
+            "warning: overlaps with {} {} - {}",
+            task.project,
+            format_timestamp(other.start_time, time_zone),
+            format_timestamp(other.end_time, time_zone)
+        );
+    }
+}
+
+/// Finds the time frame whose ID starts with `id`, returning the index of its task and its
+/// full ID.
+fn find_time_frame(tasks: &[Task], id: &str) -> Result<(usize, Uuid), Error> {
+    let mut matches = tasks
+        .iter()
+        .enumerate()
+        .flat_map(|(index, task)| {
+            task.times
+                .iter()
+                .map(move |time_frame| (index, time_frame.id))
+        })
+        .filter(|(_, time_frame_id)| time_frame_id.to_string().starts_with(id));
+
+    match (matches.next(), matches.next()) {
+        (Some(found), None) => Ok(found),
+        (None, _) => Err(Error::TimeFrameNotFound(id.to_owned())),
+        (Some(_), Some(_)) => Err(Error::AmbiguousTimeFrameId(id.to_owned())),
+    }
+}
+
+/// Names a subtask along with its project, e.g. `Acme / Design`.
+fn subtask_label(task: &Task, subtask_id: Uuid) -> String {
+    match task.subtask(subtask_id) {
+        Some(subtask) => format!("{} / {}", task.project, subtask.name),
+        None => task.project.clone(),
+    }
+}
+
+fn short_id(id: Uuid) -> String {
+    id.to_string()[..8].to_owned()
+}
+
+fn find_project(tasks: &[Task], project: &str) -> Result<usize, Error> {
+    tasks
+        .iter()
+        .position(|task| task.project == project)
+        .ok_or_else(|| Error::ProjectNotFound(project.to_owned()))
+}
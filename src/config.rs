@@ -0,0 +1,262 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use chrono::Duration;
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::{
+    report::{Grouping, ReportFormat},
+    rounding::RoundingPolicy,
+    storage::StorageKind,
+    time_zone::system_time_zone,
+    Error,
+};
+
+const APP_DIR: &str = "time-tracking";
+const CONFIG_FILE: &str = "config.toml";
+const DB_ENV_VAR: &str = "TIME_TRACKING_DB";
+const FALLBACK_DB_DIR: &str = "./data";
+/// Where earlier versions kept the DB, relative to the working directory.
+const LEGACY_DB_PATH: &str = "./data/db.json";
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub db_path: Option<PathBuf>,
+    pub storage: StorageKind,
+    /// Time frames are only merged if the `[merge]` section is present
+    pub merge: Option<MergePolicy>,
+    pub rounding: RoundingPolicy,
+    pub report: ReportConfig,
+    /// IANA name of the zone timestamps are shown and days are split in, defaulting to the
+    /// system's
+    pub time_zone: Option<Tz>,
+    pub trash: TrashConfig,
+    pub idle: IdleConfig,
+    pub long_running: LongRunningConfig,
+}
+
+/// How long deleted projects are kept.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct TrashConfig {
+    /// Days after which deleted projects are purged, or 0 to keep them until purged by hand
+    pub retention_days: i64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig { retention_days: 30 }
+    }
+}
+
+impl TrashConfig {
+    pub fn retention(&self) -> Option<Duration> {
+        (self.retention_days > 0).then(|| Duration::days(self.retention_days))
+    }
+}
+
+/// When the user counts as away while a timer is running.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct IdleConfig {
+    /// Minutes without input after which the TUI asks what to do with the time away, or 0 to
+    /// disable idle detection
+    pub threshold_minutes: i64,
+    /// Shell command printing the seconds since the last keyboard or mouse input anywhere, so
+    /// that working in other windows does not count as idle
+    pub command: Option<String>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        IdleConfig {
+            threshold_minutes: 15,
+            command: None,
+        }
+    }
+}
+
+impl IdleConfig {
+    pub fn threshold(&self) -> Option<Duration> {
+        (self.threshold_minutes > 0).then(|| Duration::minutes(self.threshold_minutes))
+    }
+}
+
+/// When a running timer counts as forgotten, to be checked on startup and by `doctor`.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct LongRunningConfig {
+    /// Hours a timer may run, or 0 for no limit
+    pub max_hours: i64,
+    /// Whether a timer still running from a previous day counts as forgotten as well
+    pub across_midnight: bool,
+}
+
+impl Default for LongRunningConfig {
+    fn default() -> Self {
+        LongRunningConfig {
+            max_hours: 10,
+            across_midnight: false,
+        }
+    }
+}
+
+impl LongRunningConfig {
+    pub fn max_duration(&self) -> Option<Duration> {
+        (self.max_hours > 0).then(|| Duration::hours(self.max_hours))
+    }
+}
+
+/// Where and how generated reports are archived.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ReportConfig {
+    pub format: ReportFormat,
+    /// Directory the reports are written to
+    pub dir: PathBuf,
+    /// File name of a report without extension, as a strftime format of the time it was
+    /// generated. `{period}` is replaced with the reported days.
+    pub name: String,
+    pub latest: LatestReport,
+    /// Rows of summary reports
+    pub group_by: Grouping,
+}
+
+/// How `latest_report.<extension>` in the report directory refers to the newest report.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LatestReport {
+    /// A symbolic link, falling back to a copy where links cannot be created
+    Symlink,
+    Copy,
+    /// Only write the timestamped report
+    None,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        ReportConfig {
+            format: ReportFormat::default(),
+            dir: "./reports".into(),
+            name: "report_%Y-%m-%d_%H%M%S_{period}".to_owned(),
+            latest: LatestReport::Symlink,
+            group_by: Grouping::default(),
+        }
+    }
+}
+
+/// Merging of time frames of the same project that are close to each other, so that rounding
+/// in reports is applied once to the combined time instead of to every short session.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MergePolicy {
+    /// Largest gap between two time frames, in minutes, for them to be merged
+    pub gap_minutes: i64,
+    pub when: MergeTime,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeTime {
+    /// Merge a time frame into the previous one when its timer is stopped
+    Stop,
+    /// Keep time frames as recorded and only merge them when generating reports
+    Report,
+}
+
+impl Default for MergePolicy {
+    fn default() -> Self {
+        MergePolicy {
+            gap_minutes: 15,
+            when: MergeTime::Report,
+        }
+    }
+}
+
+impl MergePolicy {
+    pub fn gap(&self) -> Duration {
+        Duration::minutes(self.gap_minutes)
+    }
+}
+
+impl Config {
+    /// Loads `time-tracking/config.toml` from the user's config directory, falling back to the
+    /// defaults if it does not exist.
+    pub fn load() -> Result<Self, Error> {
+        let Some(config_path) = dirs::config_dir().map(|dir| dir.join(APP_DIR).join(CONFIG_FILE))
+        else {
+            return Ok(Config::default());
+        };
+
+        match fs::read_to_string(&config_path) {
+            Ok(content) => Ok(toml::from_str(&content)?),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(Error::ReadConfigError(e)),
+        }
+    }
+
+    pub fn time_zone(&self) -> Tz {
+        self.time_zone.unwrap_or_else(system_time_zone)
+    }
+
+    /// Gap up to which a stopped time frame is merged into the previous one.
+    pub fn merge_gap_on_stop(&self) -> Option<Duration> {
+        self.merge_gap(MergeTime::Stop)
+    }
+
+    /// Gap up to which time frames are merged when generating reports.
+    pub fn merge_gap_on_report(&self) -> Option<Duration> {
+        self.merge_gap(MergeTime::Report)
+    }
+
+    fn merge_gap(&self, when: MergeTime) -> Option<Duration> {
+        self.merge
+            .as_ref()
+            .filter(|merge| merge.when == when)
+            .map(MergePolicy::gap)
+    }
+
+    /// Resolves the DB location from, in order of precedence, the `--db` flag, the
+    /// `TIME_TRACKING_DB` environment variable, the config file and the XDG data directory.
+    /// The default file name depends on the configured storage. If there is no DB at the
+    /// default location yet, but a JSON one of an earlier version, that one is kept in use.
+    pub fn db_path(&self, flag: Option<&Path>) -> PathBuf {
+        if let Some(path) = flag {
+            return path.to_owned();
+        }
+
+        if let Some(path) = std::env::var_os(DB_ENV_VAR).filter(|path| !path.is_empty()) {
+            return path.into();
+        }
+
+        if let Some(path) = &self.db_path {
+            return path.clone();
+        }
+
+        let default_path = dirs::data_dir()
+            .map(|dir| dir.join(APP_DIR))
+            .unwrap_or_else(|| FALLBACK_DB_DIR.into())
+            .join(self.storage.default_file_name());
+
+        let legacy_path = Path::new(LEGACY_DB_PATH);
+        if self.storage == StorageKind::Json
+            && !default_path.exists()
+            && legacy_path.is_file()
+            && default_path != legacy_path
+        {
+            eprintln!(
+                "warning: using the DB of an earlier version at {}; move it to {} or set db_path in the config file",
+                legacy_path.display(),
+                default_path.display()
+            );
+            return legacy_path.to_owned();
+        }
+
+        default_path
+    }
+}
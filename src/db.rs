@@ -0,0 +1,226 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    migrations::{self, SCHEMA_VERSION},
+    storage::{Data, Storage, Updater},
+    Error,
+};
+
+/// Number of rotated `.bak.N` snapshots kept next to the DB file.
+const BACKUP_COUNT: usize = 5;
+/// How long `update_db` waits for another process to release the DB lock.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Top-level layout of the DB file.
+#[derive(Serialize, Deserialize)]
+struct Database {
+    schema_version: u32,
+    #[serde(flatten)]
+    data: Data,
+}
+
+/// Stores the task list in a single JSON file, replaced atomically on every update.
+pub struct JsonStorage {
+    db_path: PathBuf,
+}
+
+impl JsonStorage {
+    pub fn new(db_path: PathBuf) -> Self {
+        JsonStorage { db_path }
+    }
+}
+
+impl Storage for JsonStorage {
+    fn read_data(&self) -> Result<Data, Error> {
+        let (data, migrated_from) = load(&self.db_path, false)?;
+
+        if migrated_from.is_some() {
+            // Persist the upgrade right away, e.g. so that newly assigned IDs are stable across
+            // runs
+            return update_db(&self.db_path, |data| data.clone());
+        }
+
+        Ok(data)
+    }
+
+    fn apply_update(&self, updater: Updater<'_>) -> Result<(), Error> {
+        update_db(&self.db_path, updater)
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.db_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn path(&self) -> &Path {
+        &self.db_path
+    }
+}
+
+fn update_db<T>(db_path: &Path, updater: impl FnOnce(&mut Data) -> T) -> Result<T, Error> {
+    // Ensure path exists
+    let db_dir = db_path.parent().unwrap_or_else(|| Path::new("./"));
+    fs::create_dir_all(db_dir)?;
+
+    // Held until the end of the read-modify-write cycle
+    let _lock = lock(db_path)?;
+
+    let (mut parsed, migrated_from) = load(db_path, true)?;
+
+    // Keep the file as it was before the upgrade, out of reach of the backup rotation
+    if let Some(version) = migrated_from {
+        fs::copy(db_path, sibling_path(db_path, &format!("v{}.bak", version)))?;
+    }
+
+    // Update data
+    let result = updater(&mut parsed);
+
+    // Write back to disk, keeping the previous version as a backup
+    let database = Database {
+        schema_version: SCHEMA_VERSION,
+        data: parsed,
+    };
+    rotate_backups(db_path)?;
+    write_atomic(db_path, &serde_json::to_vec(&database)?)?;
+
+    Ok(result)
+}
+
+/// Takes an exclusive advisory lock on `<db>.lock`, waiting up to `LOCK_TIMEOUT` for other
+/// processes to release it. The lock is released when the returned file is dropped.
+fn lock(db_path: &Path) -> Result<File, Error> {
+    let lock_path = sibling_path(db_path, "lock");
+    let lock_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)?;
+
+    let started = Instant::now();
+    loop {
+        match lock_file.try_lock() {
+            Ok(()) => return Ok(lock_file),
+            Err(TryLockError::WouldBlock) if started.elapsed() < LOCK_TIMEOUT => {
+                thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+            Err(TryLockError::WouldBlock) => return Err(Error::DBLocked(lock_path)),
+            Err(TryLockError::Error(e)) => return Err(Error::ReadDBError(e)),
+        }
+    }
+}
+
+/// Data of a DB file together with the schema version it was upgraded from, if any.
+type Loaded = (Data, Option<u32>);
+
+/// Reads and parses the DB file, upgrading it to the current schema version.
+///
+/// If the file is corrupted, the data of the newest backup that parses is returned instead.
+/// With `restore`, which requires holding the lock, the backup is also written in its place and
+/// the corrupted file is kept as `<db>.corrupt`.
+fn load(db_path: &Path, restore: bool) -> Result<Loaded, Error> {
+    let db_content = match fs::read_to_string(db_path) {
+        Ok(content) => content,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok((Data::default(), None)),
+        Err(e) => return Err(Error::ReadDBError(e)),
+    };
+
+    if db_content.is_empty() {
+        return Ok((Data::default(), None));
+    }
+
+    match parse(&db_content) {
+        Err(Error::ParseDBError(e)) => {
+            let Some((backup_content, loaded)) = newest_backup(db_path) else {
+                return Err(Error::ParseDBError(e));
+            };
+            if restore {
+                fs::copy(db_path, sibling_path(db_path, "corrupt"))?;
+                write_atomic(db_path, backup_content.as_bytes())?;
+            }
+
+            Ok(loaded)
+        }
+        loaded => loaded,
+    }
+}
+
+fn parse(db_content: &str) -> Result<Loaded, Error> {
+    let (upgraded, migrated_from) = migrations::upgrade(serde_json::from_str(db_content)?)?;
+    let database: Database = serde_json::from_value(upgraded)?;
+
+    Ok((database.data, migrated_from))
+}
+
+/// Content and data of the newest backup that parses.
+fn newest_backup(db_path: &Path) -> Option<(String, Loaded)> {
+    (1..=BACKUP_COUNT).find_map(|index| {
+        let backup_content = fs::read_to_string(backup_path(db_path, index)).ok()?;
+        let loaded = parse(&backup_content).ok()?;
+
+        Some((backup_content, loaded))
+    })
+}
+
+/// Shifts `<db>.bak.1` .. `<db>.bak.N-1` up by one and copies the current DB file to
+/// `<db>.bak.1`, dropping the oldest snapshot.
+fn rotate_backups(db_path: &Path) -> Result<(), Error> {
+    if !db_path.exists() {
+        return Ok(());
+    }
+
+    for index in (1..BACKUP_COUNT).rev() {
+        let from = backup_path(db_path, index);
+        if from.exists() {
+            fs::rename(from, backup_path(db_path, index + 1))?;
+        }
+    }
+    fs::copy(db_path, backup_path(db_path, 1))?;
+
+    Ok(())
+}
+
+/// Writes to a temporary file in the same directory, syncs it and renames it over the target,
+/// so the target is either fully replaced or left untouched. The temporary file is named after
+/// the process so that processes never write to the same one.
+fn write_atomic(path: &Path, content: &[u8]) -> Result<(), Error> {
+    let temp_path = sibling_path(path, &format!("tmp.{}", std::process::id()));
+
+    let mut temp_file = File::create(&temp_path)?;
+    temp_file.write_all(content)?;
+    temp_file.sync_all()?;
+    drop(temp_file);
+
+    fs::rename(&temp_path, path)?;
+
+    // Persist the rename itself
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+fn backup_path(db_path: &Path, index: usize) -> PathBuf {
+    sibling_path(db_path, &format!("bak.{}", index))
+}
+
+/// `<path>.<suffix>`, next to `path`.
+pub fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name: OsString = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".");
+    file_name.push(suffix);
+
+    path.with_file_name(file_name)
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+
+use crate::{config::Config, period::Period, Task};
+
+/// Every time frame of a period with its note, to show what the time was spent on.
+pub struct Details {
+    /// In the time zone days were split in
+    pub generated_at: DateTime<Tz>,
+    /// `None` if the report covers all recorded time
+    pub period: Option<Period>,
+    /// In order of their start, across all projects
+    pub entries: Vec<DetailsEntry>,
+}
+
+pub struct DetailsEntry {
+    pub project: String,
+    /// Name of the subtask
+    pub task: String,
+    pub start_time: DateTime<Tz>,
+    pub end_time: DateTime<Tz>,
+    pub note: Option<String>,
+}
+
+impl Details {
+    /// Lists the time frames of all tasks, clipped to the period if one is given. Durations are
+    /// not rounded, as rounding applies to totals.
+    pub fn build(tasks: &[Task], config: &Config, period: Option<Period>) -> Self {
+        let time_zone = config.time_zone();
+        let mut entries: Vec<_> = tasks
+            .iter()
+            .flat_map(|task| {
+                task.report_times(config.merge_gap_on_report())
+                    .into_iter()
+                    .filter_map(|time_frame| match &period {
+                        Some(period) => period.clip(&time_frame, time_zone),
+                        None => Some(time_frame),
+                    })
+                    .map(|time_frame| DetailsEntry {
+                        project: task.project.clone(),
+                        task: task
+                            .subtask(time_frame.subtask_id)
+                            .map_or_else(String::new, |subtask| subtask.name.clone()),
+                        start_time: time_frame.start_time.with_timezone(&time_zone),
+                        end_time: time_frame.end_time.with_timezone(&time_zone),
+                        note: time_frame.note,
+                    })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.start_time);
+
+        Details {
+            generated_at: Utc::now().with_timezone(&time_zone),
+            period,
+            entries,
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.entries
+            .iter()
+            .fold(Duration::zero(), |acc, entry| acc + entry.duration())
+    }
+
+    /// Describes what the report covers, e.g. `2024-05-01 to 2024-05-31`.
+    pub fn description(&self) -> String {
+        match &self.period {
+            Some(period) => period.to_string(),
+            None => "all time".to_owned(),
+        }
+    }
+}
+
+impl DetailsEntry {
+    pub fn duration(&self) -> Duration {
+        self.end_time - self.start_time
+    }
+}
@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+
+use crate::{config::Config, time_zone::local_day, Task};
+
+/// Projects whose timer has run for longer than configured, or since a previous day if that
+/// is enabled, which most likely means it was forgotten.
+pub fn long_running_timers<'a>(
+    tasks: &'a [Task],
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Vec<&'a Task> {
+    tasks
+        .iter()
+        .filter(|task| {
+            task.running_since
+                .is_some_and(|running_since| is_too_long(running_since, config, now))
+        })
+        .collect()
+}
+
+fn is_too_long(running_since: DateTime<Utc>, config: &Config, now: DateTime<Utc>) -> bool {
+    let limits = &config.long_running;
+    let time_zone = config.time_zone();
+
+    limits
+        .max_duration()
+        .is_some_and(|max_duration| now - running_since > max_duration)
+        || (limits.across_midnight
+            && local_day(running_since, time_zone) < local_day(now, time_zone))
+}
@@ -0,0 +1,408 @@
+use crate::{storage::Data, trash::TrashedTask, validate_project_name, Error, Task};
+
+/// Number of operations that can be undone.
+const HISTORY_LIMIT: usize = 100;
+
+/// A change made through the TUI, with what is needed to revert and reapply it. Edited tasks
+/// are restored from snapshots, so an operation is only reverted or reapplied while the tasks
+/// it touched have not been changed otherwise, e.g. by another process.
+#[derive(Clone)]
+pub enum Operation {
+    CreateProject {
+        task: Task,
+    },
+    /// Moving a project to the trash
+    DeleteProject {
+        trashed: TrashedTask,
+        /// Index in the task list the project was removed from
+        position: usize,
+    },
+    /// Starting or stopping timers, with every task whose timer changed
+    ToggleTimer {
+        before: Vec<Task>,
+        after: Vec<Task>,
+    },
+    /// Any change to a single project, such as renaming it or editing its time frames
+    EditProject {
+        before: Task,
+        after: Task,
+    },
+}
+
+/// Operations that can be undone and redone, most recent last.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+impl Operation {
+    /// Records how toggling timers changed the task list from `before` to `after`, or `None` if
+    /// no timer changed.
+    pub fn toggle_timer(before: &[Task], after: &[Task]) -> Option<Self> {
+        let changed: Vec<_> = after
+            .iter()
+            .filter_map(|task| {
+                let previous = before.iter().find(|previous| previous.id == task.id)?;
+                (previous != task).then(|| (previous.clone(), task.clone()))
+            })
+            .collect();
+
+        (!changed.is_empty()).then(|| {
+            let (before, after) = changed.into_iter().unzip();
+            Operation::ToggleTimer { before, after }
+        })
+    }
+
+    /// What the operation did, e.g. `deleting Acme`.
+    pub fn description(&self) -> String {
+        match self {
+            Operation::CreateProject { task } => format!("creating {}", task.project),
+            Operation::DeleteProject { trashed, .. } => {
+                format!("deleting {}", trashed.task.project)
+            }
+            Operation::ToggleTimer { after, .. } => {
+                let projects: Vec<_> = after.iter().map(|task| task.project.as_str()).collect();
+                format!("toggling the timer of {}", projects.join(", "))
+            }
+            Operation::EditProject { before, .. } => format!("changing {}", before.project),
+        }
+    }
+
+    /// Whether the data is still as the operation left it, so that reverting it loses no other
+    /// changes.
+    pub fn can_revert(&self, data: &Data) -> bool {
+        match self {
+            Operation::CreateProject { task } => {
+                matches_tasks(&data.tasks, std::slice::from_ref(task))
+            }
+            Operation::DeleteProject { trashed, .. } => data.trash.contains(trashed),
+            Operation::ToggleTimer { after, .. } => matches_tasks(&data.tasks, after),
+            Operation::EditProject { after, .. } => {
+                matches_tasks(&data.tasks, std::slice::from_ref(after))
+            }
+        }
+    }
+
+    /// Whether the data is still as reverting the operation left it, so that applying it again
+    /// loses no other changes.
+    pub fn can_apply(&self, data: &Data) -> bool {
+        match self {
+            Operation::CreateProject { task } => {
+                data.tasks.iter().all(|other| other.id != task.id)
+                    && data.trash.iter().all(|other| other.task.id != task.id)
+            }
+            Operation::DeleteProject { trashed, .. } => {
+                matches_tasks(&data.tasks, std::slice::from_ref(&trashed.task))
+            }
+            Operation::ToggleTimer { before, .. } => matches_tasks(&data.tasks, before),
+            Operation::EditProject { before, .. } => {
+                matches_tasks(&data.tasks, std::slice::from_ref(before))
+            }
+        }
+    }
+
+    /// Applies the operation again. Fails without changes if a project it brings back or
+    /// renames would take the name of another one.
+    pub fn apply(&self, data: &mut Data) -> Result<(), Error> {
+        let tasks = &mut data.tasks;
+        match self {
+            Operation::CreateProject { task } => {
+                check_name(tasks, task)?;
+                if tasks.iter().all(|other| other.id != task.id) {
+                    tasks.push(task.clone());
+                }
+            }
+            Operation::DeleteProject { trashed, .. } => {
+                let id = trashed.task.id;
+                if tasks.iter().any(|task| task.id == id) {
+                    tasks.retain(|task| task.id != id);
+                    data.trash.push(trashed.clone());
+                }
+            }
+            Operation::ToggleTimer { after, .. } => replace_tasks(tasks, after),
+            Operation::EditProject { after, .. } => {
+                check_name(tasks, after)?;
+                replace_tasks(tasks, std::slice::from_ref(after))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reverts the operation. Fails without changes if a project it brings back or renames
+    /// would take the name of another one.
+    pub fn revert(&self, data: &mut Data) -> Result<(), Error> {
+        let tasks = &mut data.tasks;
+        match self {
+            Operation::CreateProject { task } => tasks.retain(|other| other.id != task.id),
+            Operation::DeleteProject { trashed, position } => {
+                let id = trashed.task.id;
+                check_name(tasks, &trashed.task)?;
+                // Unless it has been restored or purged from the trash view in the meantime
+                if data.trash.iter().any(|other| other.task.id == id) {
+                    data.trash.retain(|other| other.task.id != id);
+                    tasks.insert((*position).min(tasks.len()), trashed.task.clone());
+                }
+            }
+            Operation::ToggleTimer { before, .. } => replace_tasks(tasks, before),
+            Operation::EditProject { before, .. } => {
+                check_name(tasks, before)?;
+                replace_tasks(tasks, std::slice::from_ref(before))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl History {
+    /// Adds an operation that was just applied, which discards the operations undone before.
+    pub fn record(&mut self, operation: Operation) {
+        self.undo.push(operation);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Takes the most recent operation for reverting. It is dropped unless passed to `undone`.
+    pub fn undo(&mut self) -> Option<Operation> {
+        self.undo.pop()
+    }
+
+    /// Adds an operation taken by `undo` that was reverted, so that it can be redone.
+    pub fn undone(&mut self, operation: Operation) {
+        self.redo.push(operation);
+    }
+
+    /// Takes the most recently undone operation for applying again. It is dropped unless passed
+    /// to `redone`.
+    pub fn redo(&mut self) -> Option<Operation> {
+        self.redo.pop()
+    }
+
+    /// Adds an operation taken by `redo` that was applied again, so that it can be undone.
+    pub fn redone(&mut self, operation: Operation) {
+        self.undo.push(operation);
+    }
+}
+
+/// Whether every task in `snapshots` still exists unchanged in `tasks`. The last activity saved
+/// for running timers is ignored, as the TUI updates it in the background.
+fn matches_tasks(tasks: &[Task], snapshots: &[Task]) -> bool {
+    snapshots.iter().all(|snapshot| {
+        tasks.iter().any(|task| {
+            task.id == snapshot.id
+                && Task {
+                    last_active: snapshot.last_active,
+                    ..task.clone()
+                } == *snapshot
+        })
+    })
+}
+
+/// Checks that putting `task` into the task list gives no two projects the same name.
+fn check_name(tasks: &[Task], task: &Task) -> Result<(), Error> {
+    validate_project_name(tasks, &task.project, Some(task.id)).map(|_| ())
+}
+
+/// Replaces the tasks with the same IDs as `snapshots`. Tasks deleted in the meantime stay
+/// deleted.
+fn replace_tasks(tasks: &mut [Task], snapshots: &[Task]) {
+    for snapshot in snapshots {
+        if let Some(task) = tasks.iter_mut().find(|task| task.id == snapshot.id) {
+            *task = snapshot.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::toggle_timer;
+
+    fn to_json(data: &Data) -> serde_json::Value {
+        serde_json::to_value(data).unwrap()
+    }
+
+    fn sample_data() -> Data {
+        Data {
+            tasks: vec![
+                Task::new("Website".to_owned()),
+                Task::new("Mobile app".to_owned()),
+            ],
+            trash: vec![],
+        }
+    }
+
+    /// Changes `data` like the TUI does and returns the operation it records.
+    fn create(data: &mut Data) -> Operation {
+        let task = Task::new("Backend".to_owned());
+        data.tasks.push(task.clone());
+
+        Operation::CreateProject { task }
+    }
+
+    fn delete(data: &mut Data) -> Operation {
+        let id = data.tasks[0].id;
+        let position = data.trash_task(id, None).unwrap();
+        let trashed = data.trash.last().cloned().unwrap();
+
+        Operation::DeleteProject { trashed, position }
+    }
+
+    fn toggle(data: &mut Data) -> Operation {
+        let before = data.tasks.clone();
+        let subtask_id = data.tasks[0].subtasks[0].id;
+        toggle_timer(&mut data.tasks, 0, subtask_id, None);
+
+        Operation::toggle_timer(&before, &data.tasks).unwrap()
+    }
+
+    fn edit(data: &mut Data) -> Operation {
+        let before = data.tasks[0].clone();
+        data.tasks[0].project = "Homepage".to_owned();
+        data.tasks[0].tags = vec!["web".to_owned()];
+
+        Operation::EditProject {
+            before,
+            after: data.tasks[0].clone(),
+        }
+    }
+
+    /// Checks that undoing restores the data from before the change and redoing the data
+    /// after it.
+    fn assert_undo_and_redo(change: fn(&mut Data) -> Operation) {
+        let mut data = sample_data();
+        let before = to_json(&data);
+        let operation = change(&mut data);
+        let after = to_json(&data);
+
+        assert!(operation.can_revert(&data));
+        operation.revert(&mut data).unwrap();
+        assert_eq!(to_json(&data), before);
+
+        assert!(operation.can_apply(&data));
+        operation.apply(&mut data).unwrap();
+        assert_eq!(to_json(&data), after);
+    }
+
+    #[test]
+    fn undoes_and_redoes_creating_a_project() {
+        assert_undo_and_redo(create);
+    }
+
+    #[test]
+    fn undoes_and_redoes_deleting_a_project() {
+        assert_undo_and_redo(delete);
+    }
+
+    #[test]
+    fn undoes_and_redoes_toggling_a_timer() {
+        assert_undo_and_redo(toggle);
+    }
+
+    #[test]
+    fn undoes_and_redoes_editing_a_project() {
+        assert_undo_and_redo(edit);
+    }
+
+    #[test]
+    fn refuses_to_undo_after_an_external_edit() {
+        // With the index of the task each change touches
+        for (change, touched) in [
+            (create as fn(&mut Data) -> Operation, 2),
+            (toggle, 0),
+            (edit, 0),
+        ] {
+            let mut data = sample_data();
+            let operation = change(&mut data);
+
+            data.tasks[touched].client = Some("Acme".to_owned());
+
+            assert!(!operation.can_revert(&data));
+        }
+    }
+
+    #[test]
+    fn refuses_to_redo_after_an_external_edit() {
+        let mut data = sample_data();
+        let operation = edit(&mut data);
+        operation.revert(&mut data).unwrap();
+
+        data.tasks[0].client = Some("Acme".to_owned());
+
+        assert!(!operation.can_apply(&data));
+    }
+
+    #[test]
+    fn ignores_saved_activity_of_running_timers() {
+        let mut data = sample_data();
+        let operation = toggle(&mut data);
+
+        data.tasks[0].last_active = Some(Utc::now() + Duration::minutes(5));
+
+        assert!(operation.can_revert(&data));
+    }
+
+    #[test]
+    fn refuses_to_undo_a_delete_after_the_project_was_restored() {
+        let mut data = sample_data();
+        let operation = delete(&mut data);
+
+        let trashed = data.trash.remove(0);
+        data.tasks.push(trashed.task);
+
+        assert!(!operation.can_revert(&data));
+    }
+
+    #[test]
+    fn refuses_to_undo_a_delete_after_the_project_was_purged() {
+        let mut data = sample_data();
+        let operation = delete(&mut data);
+
+        data.trash.clear();
+
+        assert!(!operation.can_revert(&data));
+    }
+
+    #[test]
+    fn refuses_to_undo_a_delete_if_the_name_was_taken() {
+        let mut data = sample_data();
+        let operation = delete(&mut data);
+        data.tasks.push(Task::new("Website".to_owned()));
+        let expected = to_json(&data);
+
+        assert!(matches!(
+            operation.revert(&mut data),
+            Err(Error::ProjectExists(name)) if name == "Website"
+        ));
+        assert_eq!(to_json(&data), expected);
+    }
+
+    #[test]
+    fn undoes_and_redoes_in_order() {
+        let mut history = History::default();
+        let mut data = sample_data();
+        history.record(create(&mut data));
+        history.record(edit(&mut data));
+
+        let operation = history.undo().unwrap();
+        assert!(matches!(operation, Operation::EditProject { .. }));
+        history.undone(operation);
+
+        let operation = history.redo().unwrap();
+        assert!(matches!(operation, Operation::EditProject { .. }));
+        history.redone(operation);
+        assert!(history.redo().is_none());
+
+        // Recording a new change discards what was undone
+        let operation = history.undo().unwrap();
+        history.undone(operation);
+        history.record(toggle(&mut data));
+        assert!(history.redo().is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    io::Read,
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Instant,
+};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{config::IdleConfig, Error};
+
+/// How often the idle command is run.
+const COMMAND_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long the idle command may take before it is killed.
+const COMMAND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A time the user was away from a running timer.
+pub struct Away {
+    pub since: DateTime<Utc>,
+    /// When the user came back
+    pub until: DateTime<Utc>,
+}
+
+/// Notices when the user was away while a timer was running, based on key presses in the TUI
+/// and, if configured, the idle time of the whole system.
+pub struct IdleTracker {
+    threshold: Option<Duration>,
+    /// Dropped after it failed once
+    command: Option<String>,
+    /// Last key press, or last input anywhere as reported by the idle command
+    last_activity: DateTime<Utc>,
+    /// Start of the time away, once it exceeded the threshold with a timer running
+    idle_since: Option<DateTime<Utc>>,
+    command_run_at: Option<Instant>,
+    /// Result of the idle command running in the background, with the time it was started
+    pending: Option<Receiver<Result<Duration, Error>>>,
+    pending_since: DateTime<Utc>,
+}
+
+impl IdleTracker {
+    pub fn new(config: &IdleConfig) -> Self {
+        IdleTracker {
+            threshold: config.threshold(),
+            command: config.command.clone(),
+            last_activity: Utc::now(),
+            idle_since: None,
+            command_run_at: None,
+            pending: None,
+            pending_since: Utc::now(),
+        }
+    }
+
+    pub fn last_activity(&self) -> DateTime<Utc> {
+        self.last_activity
+    }
+
+    /// Records a key press, returning the time away it ended, if any.
+    pub fn input(&mut self, now: DateTime<Utc>, timer_running: bool) -> Option<Away> {
+        self.mark_idle(now, timer_running);
+        self.last_activity = now;
+
+        self.idle_since
+            .take()
+            .map(|since| Away { since, until: now })
+    }
+
+    /// Called periodically to run the idle command when due and collect its result, without
+    /// waiting for it. Returns the time away if the command reports input since it began.
+    pub fn tick(&mut self, now: DateTime<Utc>, timer_running: bool) -> Result<Option<Away>, Error> {
+        let result = self.run_command(now);
+        self.mark_idle(now, timer_running);
+
+        let away = match self.idle_since {
+            Some(since) if self.last_activity > since => {
+                self.idle_since = None;
+                Some(Away {
+                    since,
+                    until: self.last_activity,
+                })
+            }
+            _ => None,
+        };
+
+        result.map(|()| away)
+    }
+
+    fn mark_idle(&mut self, now: DateTime<Utc>, timer_running: bool) {
+        if self.idle_since.is_none()
+            && timer_running
+            && self
+                .threshold
+                .is_some_and(|threshold| now - self.last_activity >= threshold)
+        {
+            self.idle_since = Some(self.last_activity);
+        }
+    }
+
+    fn run_command(&mut self, now: DateTime<Utc>) -> Result<(), Error> {
+        if let Some(pending) = &self.pending {
+            let result = match pending.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => Err(Error::IdleCommandError(
+                    "the command stopped unexpectedly".to_owned(),
+                )),
+            };
+            self.pending = None;
+
+            match result {
+                Ok(idle_time) => {
+                    self.last_activity = self.last_activity.max(self.pending_since - idle_time);
+                }
+                Err(e) => {
+                    self.command = None;
+                    return Err(e);
+                }
+            }
+        }
+
+        let Some(command) = &self.command else {
+            return Ok(());
+        };
+        if self.threshold.is_none()
+            || self
+                .command_run_at
+                .is_some_and(|run_at| run_at.elapsed() < COMMAND_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.command_run_at = Some(Instant::now());
+
+        let (sender, receiver) = mpsc::channel();
+        let command = command.clone();
+        thread::spawn(move || {
+            // The receiver is gone if the TUI was closed meanwhile
+            let _ = sender.send(system_idle_time(&command));
+        });
+        self.pending = Some(receiver);
+        self.pending_since = now;
+
+        Ok(())
+    }
+}
+
+/// Runs the idle command with `sh -c` and parses the seconds since the last input it prints.
+/// The command is killed if it does not finish within `COMMAND_TIMEOUT`.
+fn system_idle_time(command: &str) -> Result<Duration, Error> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| Error::IdleCommandError(e.to_string()))?;
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| Error::IdleCommandError(e.to_string()))?
+        {
+            break status;
+        }
+        if started.elapsed() >= COMMAND_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Error::IdleCommandError(format!(
+                "no result after {} seconds",
+                COMMAND_TIMEOUT.as_secs()
+            )));
+        }
+        thread::sleep(std::time::Duration::from_millis(50));
+    };
+    if !status.success() {
+        return Err(Error::IdleCommandError(status.to_string()));
+    }
+
+    let mut stdout = String::new();
+    if let Some(mut output) = child.stdout.take() {
+        output
+            .read_to_string(&mut stdout)
+            .map_err(|e| Error::IdleCommandError(e.to_string()))?;
+    }
+    let seconds: f64 = stdout.trim().parse().map_err(|_| {
+        Error::IdleCommandError(format!(
+            "expected a number of seconds, got \"{}\"",
+            stdout.trim()
+        ))
+    })?;
+
+    Ok(Duration::milliseconds((seconds.max(0.0) * 1000.0) as i64))
+}
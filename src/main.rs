@@ -1,52 +1,223 @@
-use std::{
-    fs::{self, OpenOptions},
-    io::{self, Read},
-    path::PathBuf,
-    sync::mpsc,
-    thread,
-    time::Instant,
-};
-
-use chrono::{DateTime, Duration, Utc};
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+mod app;
+mod cli;
+mod config;
+mod db;
+mod details;
+mod doctor;
+mod history;
+mod idle;
+mod migrations;
+mod period;
+mod render;
+mod report;
+mod rounding;
+mod sqlite;
+mod storage;
+#[cfg(test)]
+mod test_support;
+mod time_zone;
+mod timesheet;
+mod trash;
+mod ui;
+
+use std::{collections::HashMap, io, path::PathBuf};
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use clap::Parser;
+use config::Config;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tui::{
-    backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    text::Span,
-    widgets::{Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
-    Terminal,
-};
-
-const DB_PATH: &str = "./data/db.json";
-
-#[derive(Serialize, Deserialize, Clone)]
+use uuid::Uuid;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Name of the task every project starts out with.
+const DEFAULT_SUBTASK: &str = "General";
+
+/// Note of the time frames that idle time is split into.
+const IDLE_NOTE: &str = "Idle";
+
+/// A project. Its time is tracked on its subtasks, but kept in one list so that reports and
+/// totals roll up to the project.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 struct Task {
-    id: usize,
+    id: Uuid,
     project: String,
     created_at: DateTime<Utc>,
     running_since: Option<DateTime<Utc>>,
+    /// The subtask the timer runs on, set along with `running_since`
+    running_subtask: Option<Uuid>,
+    /// Last input noticed by the TUI while the timer was running, saved every few minutes so
+    /// that a forgotten timer can be stopped there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_active: Option<DateTime<Utc>>,
+    /// Set when the timer was started again after idle time was removed, so that the time frame
+    /// it records is not merged across the idle time
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    resumed_after_idle: bool,
+    /// Never empty
+    subtasks: Vec<Subtask>,
     times: Vec<TimeFrame>,
+    /// Hidden from the projects table, but still included in reports
+    #[serde(default)]
+    archived: bool,
+    /// Client or category the project belongs to
+    #[serde(default)]
+    client: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct Subtask {
+    id: Uuid,
+    name: String,
+    created_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 struct TimeFrame {
-    id: usize,
+    id: Uuid,
+    /// The subtask of the project the time was spent on
+    subtask_id: Uuid,
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
+    /// What was done, left out of the file unless entered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    /// Recorded by a timer started again after idle time, which is never merged into the time
+    /// frames before it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    after_idle: bool,
 }
 
 impl Task {
+    fn new(project: String) -> Self {
+        Task {
+            id: Uuid::new_v4(),
+            project,
+            created_at: Utc::now(),
+            running_since: None,
+            running_subtask: None,
+            last_active: None,
+            resumed_after_idle: false,
+            subtasks: vec![Subtask::new(DEFAULT_SUBTASK.to_owned())],
+            times: vec![],
+            archived: false,
+            client: None,
+            tags: vec![],
+        }
+    }
+
     fn is_running(&self) -> bool {
         self.running_since.is_some()
     }
 
+    fn is_running_subtask(&self, subtask_id: Uuid) -> bool {
+        self.is_running() && self.running_subtask == Some(subtask_id)
+    }
+
+    /// Starts the timer on a subtask, which must not be running yet.
+    fn start(&mut self, subtask_id: Uuid) {
+        self.running_since = Some(Utc::now());
+        self.running_subtask = Some(subtask_id);
+        self.last_active = None;
+        self.resumed_after_idle = false;
+    }
+
+    /// When the user was last seen working on the running timer: the last input saved by the
+    /// TUI, or else the start of the timer.
+    fn last_activity(&self) -> Option<DateTime<Utc>> {
+        self.running_since.map(|running_since| {
+            self.last_active
+                .map_or(running_since, |last_active| last_active.max(running_since))
+        })
+    }
+
+    fn subtask(&self, subtask_id: Uuid) -> Option<&Subtask> {
+        self.subtasks
+            .iter()
+            .find(|subtask| subtask.id == subtask_id)
+    }
+
+    fn find_subtask(&self, name: &str) -> Result<&Subtask, Error> {
+        self.subtasks
+            .iter()
+            .find(|subtask| subtask.name == name)
+            .ok_or_else(|| Error::SubtaskNotFound(self.project.clone(), name.to_owned()))
+    }
+
+    /// The subtask a timer started on the project as a whole runs on: the running one, or else
+    /// the one time was recorded on last.
+    fn current_subtask(&self) -> Uuid {
+        self.running_subtask
+            .filter(|_| self.is_running())
+            .or_else(|| {
+                self.times
+                    .iter()
+                    .max_by_key(|time_frame| time_frame.end_time)
+                    .map(|time_frame| time_frame.subtask_id)
+            })
+            .filter(|subtask_id| self.subtask(*subtask_id).is_some())
+            .unwrap_or(self.subtasks[0].id)
+    }
+
+    fn add_subtask(&mut self, name: String) -> Result<Uuid, Error> {
+        if self.subtasks.iter().any(|subtask| subtask.name == name) {
+            return Err(Error::SubtaskExists(self.project.clone(), name));
+        }
+        let subtask = Subtask::new(name);
+        let id = subtask.id;
+        self.subtasks.push(subtask);
+
+        Ok(id)
+    }
+
+    /// Deletes a subtask, stopping its timer and moving its time frames to the first remaining
+    /// subtask so that no recorded time is lost.
+    fn delete_subtask(
+        &mut self,
+        subtask_id: Uuid,
+        merge_gap: Option<Duration>,
+    ) -> Result<(), Error> {
+        if self.subtasks.len() <= 1 {
+            return Err(Error::LastSubtask);
+        }
+
+        if self.is_running_subtask(subtask_id) {
+            self.stop(merge_gap);
+        }
+        self.subtasks.retain(|subtask| subtask.id != subtask_id);
+        let remaining_id = self.subtasks[0].id;
+        for time_frame in self
+            .times
+            .iter_mut()
+            .filter(|time_frame| time_frame.subtask_id == subtask_id)
+        {
+            time_frame.subtask_id = remaining_id;
+        }
+
+        Ok(())
+    }
+
+    /// Recorded time of a subtask, including its running timer.
+    fn subtask_duration(&self, subtask_id: Uuid) -> Duration {
+        let past_duration = self
+            .times
+            .iter()
+            .filter(|time_frame| time_frame.subtask_id == subtask_id)
+            .fold(Duration::zero(), |acc, time_frame| {
+                acc + time_frame.duration()
+            });
+
+        if self.is_running_subtask(subtask_id) {
+            past_duration + self.current_duration()
+        } else {
+            past_duration
+        }
+    }
+
     fn current_duration(&self) -> Duration {
         if let Some(running_since) = self.running_since {
             Utc::now() - running_since
@@ -62,487 +233,463 @@ impl Task {
 
         Duration::seconds(past_duration + self.current_duration().num_seconds())
     }
-}
 
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("error reading the DB file: {0}")]
-    ReadDBError(#[from] io::Error),
-    #[error("error parsing the DB file: {0}")]
-    ParseDBError(#[from] serde_json::Error),
-}
+    /// Time frames to report, with those at most `merge_gap` apart merged so that the gaps
+    /// between them count as well. A running timer is included up to now.
+    fn report_times(&self, merge_gap: Option<Duration>) -> Vec<TimeFrame> {
+        let mut times = match merge_gap {
+            Some(gap) => self.merged_times(gap),
+            None => self.times.clone(),
+        };
 
-enum Event<I> {
-    Input(I),
-    Tick,
-}
-
-struct App {
-    state: State,
-}
+        if let Some(running_since) = self.running_since {
+            times.push(TimeFrame {
+                id: Uuid::nil(),
+                subtask_id: self.current_subtask(),
+                start_time: running_since,
+                end_time: Utc::now(),
+                note: None,
+                after_idle: self.resumed_after_idle,
+            });
+        }
 
-enum State {
-    Projects,
-    Help,
-    CreateProject { input: String },
-    DeleteProject,
-}
+        times
+    }
 
-enum Transitions {
-    CreateNew,
-    Delete,
-    Escape,
-    ShowHelp,
-    InputCharacter(char),
-}
+    /// Stops the timer and records the elapsed time. If the previous time frame of the same
+    /// subtask ended at most `merge_gap` before the timer was started, it is extended instead of
+    /// adding a new one, unless idle time was removed in between.
+    fn stop(&mut self, merge_gap: Option<Duration>) -> Option<Uuid> {
+        self.stop_at(Utc::now(), merge_gap)
+    }
 
-impl App {
-    fn transition(&mut self, transition: Transitions) {
-        match (&self.state, transition) {
-            (State::Projects, Transitions::CreateNew) => {
-                self.state = State::CreateProject {
-                    input: String::new(),
-                }
+    /// Like `stop`, but records the time up to `end_time` instead of now. Returns the ID of the
+    /// time frame the time was recorded in, or `None` if the timer was not running.
+    fn stop_at(&mut self, end_time: DateTime<Utc>, merge_gap: Option<Duration>) -> Option<Uuid> {
+        let subtask_id = self.current_subtask();
+        let running_since = self.running_since.take()?;
+        self.running_subtask = None;
+        self.last_active = None;
+        let after_idle = std::mem::take(&mut self.resumed_after_idle);
+        let new_time_frame = TimeFrame {
+            id: Uuid::new_v4(),
+            subtask_id,
+            start_time: running_since,
+            end_time,
+            note: None,
+            after_idle,
+        };
+
+        let previous = self
+            .times
+            .iter_mut()
+            .filter(|time_frame| time_frame.subtask_id == subtask_id && !time_frame.is_idle())
+            .max_by_key(|time_frame| time_frame.end_time)
+            .filter(|_| !after_idle);
+        match (previous, merge_gap) {
+            (Some(previous), Some(gap)) if new_time_frame.start_time - previous.end_time <= gap => {
+                previous.end_time = previous.end_time.max(new_time_frame.end_time);
+                Some(previous.id)
             }
-            (State::Projects, Transitions::Delete) => {
-                self.state = State::DeleteProject;
+            _ => {
+                let id = new_time_frame.id;
+                self.times.push(new_time_frame);
+                Some(id)
             }
-            (State::Projects, Transitions::ShowHelp) => {
-                self.state = State::Help;
-            }
-            (State::Help, Transitions::Escape) => {
-                self.state = State::Projects;
+        }
+    }
+
+    /// Stops the timer at `idle_since` and starts it again on the same subtask at `until`, when
+    /// the user came back. The time in between is dropped, or recorded as a separate time frame
+    /// if `split` is set. Neither is merged away by later stops or in reports.
+    fn remove_idle_time(
+        &mut self,
+        idle_since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        split: bool,
+        merge_gap: Option<Duration>,
+    ) {
+        if !self.is_running() {
+            return;
+        }
+
+        let subtask_id = self.current_subtask();
+        self.stop_at(idle_since, merge_gap);
+        if split {
+            self.times.push(TimeFrame {
+                id: Uuid::new_v4(),
+                subtask_id,
+                start_time: idle_since,
+                end_time: until,
+                note: Some(IDLE_NOTE.to_owned()),
+                after_idle: false,
+            });
+        }
+        self.running_since = Some(until);
+        self.running_subtask = Some(subtask_id);
+        self.resumed_after_idle = true;
+    }
+
+    /// Input noticed while the timer was running, unless there was none since its start.
+    fn recorded_activity(&self) -> Option<DateTime<Utc>> {
+        self.last_activity()
+            .filter(|last_activity| Some(*last_activity) > self.running_since)
+    }
+
+    /// When to stop a timer left running at its last activity. Fails if no input was noticed
+    /// while it ran, as stopping it at its start would record no time.
+    fn last_activity_stop_time(&self) -> Result<DateTime<Utc>, Error> {
+        self.recorded_activity()
+            .ok_or_else(|| Error::NoActivityRecorded(self.project.clone()))
+    }
+
+    /// Checks that a timer left running can be stopped at `end_time`, which must be after its
+    /// start and not after now.
+    fn check_stop_time(&self, end_time: DateTime<Utc>) -> Result<(), Error> {
+        match self.running_since {
+            Some(running_since) if end_time < running_since => {
+                return Err(Error::StopBeforeStart(self.project.clone()));
             }
-            (State::CreateProject { input }, Transitions::InputCharacter(character)) => {
-                self.state = State::CreateProject {
-                    input: format!("{}{}", input, character),
-                }
+            Some(running_since) if end_time == running_since => {
+                return Err(Error::StopAtStart(self.project.clone()));
             }
-            (State::CreateProject { input }, Transitions::Delete) => {
-                self.state = State::CreateProject {
-                    input: input[0..input.len() - 1].to_owned(),
-                }
+            _ => {}
+        }
+        if end_time > Utc::now() {
+            return Err(Error::StopInFuture);
+        }
+
+        Ok(())
+    }
+
+    /// Recorded time frames with those at most `gap` apart merged into one.
+    fn merged_times(&self, gap: Duration) -> Vec<TimeFrame> {
+        group_adjacent(&self.times, gap)
+            .iter()
+            .map(|group| merge_group(group))
+            .collect()
+    }
+}
+
+impl Subtask {
+    fn new(name: String) -> Self {
+        Subtask {
+            id: Uuid::new_v4(),
+            name,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Splits time frames into groups, ordered by start time, in which every time frame belongs
+/// to the same subtask and starts at most `gap` after the previous ones ended. Idle time
+/// frames and those recorded after idle time are not added to earlier groups, and idle ones
+/// keep a group of their own.
+fn group_adjacent(times: &[TimeFrame], gap: Duration) -> Vec<Vec<&TimeFrame>> {
+    let mut sorted: Vec<_> = times.iter().collect();
+    sorted.sort_by_key(|time_frame| time_frame.start_time);
+
+    let mut groups: Vec<Vec<&TimeFrame>> = vec![];
+    // Index and end of the last group of each subtask
+    let mut open_groups: HashMap<Uuid, (usize, DateTime<Utc>)> = HashMap::new();
+    for time_frame in sorted {
+        let open_group = open_groups
+            .get(&time_frame.subtask_id)
+            .filter(|_| !time_frame.after_idle && !time_frame.is_idle());
+        match open_group {
+            Some(&(index, end)) if time_frame.start_time - end <= gap => {
+                groups[index].push(time_frame);
+                open_groups.insert(time_frame.subtask_id, (index, end.max(time_frame.end_time)));
             }
-            (State::CreateProject { input: _ }, Transitions::Escape) => {
-                self.state = State::Projects;
+            _ if time_frame.is_idle() => {
+                // Nothing is merged across idle time
+                groups.push(vec![time_frame]);
+                open_groups.remove(&time_frame.subtask_id);
             }
-            (State::DeleteProject, Transitions::Escape) => {
-                self.state = State::Projects;
+            _ => {
+                groups.push(vec![time_frame]);
+                open_groups.insert(
+                    time_frame.subtask_id,
+                    (groups.len() - 1, time_frame.end_time),
+                );
             }
-            (_, _) => {}
         }
     }
+
+    groups
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    enable_raw_mode().expect("can run in raw mode");
-
-    let (tx, rx) = mpsc::channel();
-    let tick_rate = std::time::Duration::from_millis(200);
-    thread::spawn(move || {
-        let mut last_tick = Instant::now();
-        loop {
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| std::time::Duration::from_secs(0));
-
-            if event::poll(timeout).expect("poll works") {
-                if let CEvent::Key(key) = event::read().expect("can read events") {
-                    tx.send(Event::Input(key)).expect("can send events");
-                }
-            }
+/// Combines a group from `group_adjacent` into one time frame, keeping the first one's ID and
+/// joining the distinct notes.
+fn merge_group(group: &[&TimeFrame]) -> TimeFrame {
+    let mut notes: Vec<&str> = vec![];
+    for note in group
+        .iter()
+        .filter_map(|time_frame| time_frame.note.as_deref())
+    {
+        if !notes.contains(&note) {
+            notes.push(note);
+        }
+    }
 
-            if last_tick.elapsed() >= tick_rate {
-                if let Ok(_) = tx.send(Event::Tick) {
-                    last_tick = Instant::now();
-                }
-            }
+    TimeFrame {
+        id: group[0].id,
+        subtask_id: group[0].subtask_id,
+        start_time: group[0].start_time,
+        end_time: group
+            .iter()
+            .map(|time_frame| time_frame.end_time)
+            .max()
+            .unwrap_or(group[0].end_time),
+        note: (!notes.is_empty()).then(|| notes.join("; ")),
+        after_idle: group[0].after_idle,
+    }
+}
+
+impl TimeFrame {
+    fn new(
+        subtask_id: Uuid,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Self, Error> {
+        if end_time <= start_time {
+            return Err(Error::InvalidTimeFrame);
         }
-    });
-
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    terminal.clear()?;
-
-    let mut app = App {
-        state: State::Projects,
-    };
-
-    let mut task_list_state = TableState::default();
-    task_list_state.select(Some(0));
-
-    loop {
-        terminal.draw(|rect| {
-            let size = rect.size();
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(2)
-                .constraints(
-                    [
-                        Constraint::Length(3),
-                        Constraint::Min(2),
-                        Constraint::Length(3),
-                    ]
-                    .as_ref(),
-                )
-                .split(size);
-
-            let contextual_help = Paragraph::new("q: Quit | ?: Show help")
-                .style(Style::default().fg(Color::LightCyan))
-                .alignment(Alignment::Center)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .style(Style::default().fg(Color::White))
-                        .title("Shortcuts")
-                        .border_type(BorderType::Plain),
-                );
 
-            rect.render_widget(contextual_help, chunks[0]);
+        Ok(TimeFrame {
+            id: Uuid::new_v4(),
+            subtask_id,
+            start_time,
+            end_time,
+            note: None,
+            after_idle: false,
+        })
+    }
 
-            let copyright = Paragraph::new("Time Tracking CLI")
-                .style(Style::default().fg(Color::LightCyan))
-                .alignment(Alignment::Center)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .style(Style::default().fg(Color::White))
-                        .border_type(BorderType::Plain),
-                );
+    fn duration(&self) -> Duration {
+        self.end_time - self.start_time
+    }
 
-            rect.render_widget(copyright, chunks[2]);
-
-            match &app.state {
-                State::Projects => {
-                    let task_details = render_tasks();
-                    rect.render_stateful_widget(task_details, chunks[1], &mut task_list_state);
-                }
-                State::Help => {
-                    let help_popup = render_help_popup();
-                    let area = centered_rect(40, 40, chunks[1]);
-
-                    rect.render_widget(Clear, chunks[1]);
-                    rect.render_widget(help_popup, area);
-                }
-                State::CreateProject { input } => {
-                    let popup_input_field = render_create_popup(input);
-                    let area = centered_rect(20, 20, chunks[1]);
-
-                    rect.render_widget(Clear, chunks[1]);
-                    rect.render_widget(
-                        popup_input_field,
-                        Rect {
-                            x: area.x,
-                            y: area.y,
-                            height: 3,
-                            width: area.width,
-                        },
-                    );
-                }
-                State::DeleteProject => {
-                    let popup_input_field = render_delete_project_popup();
-                    let area = centered_rect(40, 20, chunks[1]);
-
-                    rect.render_widget(Clear, chunks[1]);
-                    rect.render_widget(
-                        popup_input_field,
-                        Rect {
-                            x: area.x,
-                            y: area.y,
-                            height: 3,
-                            width: area.width,
-                        },
-                    )
-                }
-            }
-        })?;
-
-        match rx.recv()? {
-            Event::Input(event) => match &app.state {
-                State::Projects => match event.code {
-                    KeyCode::Char('q') => {
-                        disable_raw_mode()?;
-                        execute!(
-                            terminal.backend_mut(),
-                            LeaveAlternateScreen,
-                            DisableMouseCapture
-                        )?;
-                        terminal.show_cursor()?;
-                        break;
-                    }
-                    KeyCode::Down | KeyCode::Char('j') => {
-                        if let Some(selected) = task_list_state.selected() {
-                            let amount_tasks = read_db().expect("can fetch task list").len();
-                            if selected >= amount_tasks - 1 {
-                                task_list_state.select(Some(0));
-                            } else {
-                                task_list_state.select(Some(selected + 1));
-                            }
-                        }
-                    }
-                    KeyCode::Up | KeyCode::Char('k') => {
-                        if let Some(selected) = task_list_state.selected() {
-                            let amount_tasks = read_db().expect("can fetch task list").len();
-                            if selected > 0 {
-                                task_list_state.select(Some(selected - 1));
-                            } else {
-                                task_list_state.select(Some(amount_tasks - 1));
-                            }
-                        }
-                    }
-                    KeyCode::Char(' ') | KeyCode::Enter => {
-                        if let Some(selected) = task_list_state.selected() {
-                            update_db(|tasks| {
-                                let is_running = tasks[selected].is_running();
-
-                                for task in tasks.iter_mut() {
-                                    if let Some(running_since) = task.running_since {
-                                        task.running_since = None;
-
-                                        // TODO: Merge time frames that are within 15 minutes of
-                                        // each other to help with fair rounding.
-
-                                        let new_time_frame = TimeFrame {
-                                            id: task.times.len(),
-                                            start_time: running_since,
-                                            end_time: Utc::now(),
-                                        };
-
-                                        task.times.push(new_time_frame);
-                                    }
-                                }
-
-                                if !is_running {
-                                    let mut selected_task =
-                                        tasks.get_mut(selected).expect("exists");
-                                    selected_task.running_since = Some(Utc::now());
-                                }
-                            })?;
-                        }
-                    }
-                    KeyCode::Char('a') => {
-                        app.transition(Transitions::CreateNew);
-                    }
-                    KeyCode::Char('d') => {
-                        app.transition(Transitions::Delete);
-                    }
-                    KeyCode::Char('r') => {
-                        let tasks = read_db()?;
-
-                        let mut csv = String::new();
-
-                        csv.push_str("Project,Duration\n");
-
-                        for task in tasks {
-                            csv.push_str(&format!(
-                                "{},{}\n",
-                                task.project,
-                                format_duration_report(task.total_duration())
-                            ));
-                        }
-
-                        fs::create_dir_all("./reports")?;
-                        fs::write("./reports/latest_report.csv", csv)?;
-                    }
-                    KeyCode::Char('?') => {
-                        app.transition(Transitions::ShowHelp);
-                    }
-                    KeyCode::Esc => {
-                        app.transition(Transitions::Escape);
-                    }
-                    _ => {}
-                },
-                State::CreateProject { input } => match event.code {
-                    KeyCode::Enter => {
-                        update_db(|tasks| {
-                            tasks.push(Task {
-                                id: tasks.len(),
-                                project: input.clone(),
-                                times: vec![],
-                                created_at: Utc::now(),
-                                running_since: None,
-                            });
-                        })?;
-
-                        app.transition(Transitions::Escape);
-                    }
-                    KeyCode::Char(c) => {
-                        app.transition(Transitions::InputCharacter(c));
-                    }
-                    KeyCode::Backspace => {
-                        app.transition(Transitions::Delete);
-                    }
-                    KeyCode::Esc => {
-                        app.transition(Transitions::Escape);
-                    }
-                    _ => {}
-                },
-                State::DeleteProject => match event.code {
-                    KeyCode::Esc | KeyCode::Char('n' | 'q') => {
-                        app.transition(Transitions::Escape);
-                    }
-                    KeyCode::Char('y') => {
-                        update_db(|tasks| {
-                            if let Some(selected) = task_list_state.selected() {
-                                let _ = tasks.remove(selected);
-                            }
-                        })?;
-
-                        app.transition(Transitions::Escape);
-                    }
-                    _ => {}
-                },
-                State::Help => match event.code {
-                    KeyCode::Esc | KeyCode::Char('?' | 'q') => {
-                        app.transition(Transitions::Escape);
-                    }
-                    _ => {}
-                },
-            },
-            Event::Tick => {}
-        }
+    /// Whether this is idle time split off from a timer.
+    fn is_idle(&self) -> bool {
+        self.note.as_deref() == Some(IDLE_NOTE)
     }
 
-    Ok(())
+    fn overlaps(&self, other: &TimeFrame) -> bool {
+        self.start_time < other.end_time && other.start_time < self.end_time
+    }
 }
 
-fn render_help_popup<'a>() -> Table<'a> {
-    Table::new(vec![
-        Row::new(vec![
-            Cell::from(Span::raw("a")),
-            Cell::from(Span::raw("Add new project")),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::raw("d")),
-            Cell::from(Span::raw("Delete selected project")),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::raw("<space>")),
-            Cell::from(Span::raw("Start/stop project timer")),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::raw("r")),
-            Cell::from(Span::raw("Generate a report")),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::raw("<esc>")),
-            Cell::from(Span::raw("Close help")),
-        ]),
-    ])
-    .header(Row::new(vec![
-        Cell::from(Span::raw("Shortcut")),
-        Cell::from(Span::raw("Description")),
-    ]))
-    .widths(&[Constraint::Percentage(20), Constraint::Percentage(80)])
-    .block(Block::default().title("Help").borders(Borders::ALL))
-}
+impl Task {
+    fn add_time_frame(&mut self, time_frame: TimeFrame) {
+        self.times.push(time_frame);
+        self.times.sort_by_key(|time_frame| time_frame.start_time);
+    }
+
+    /// Replaces the start, end and subtask of a recorded time frame, keeping its ID and note.
+    fn edit_time_frame(&mut self, edited: TimeFrame) -> Result<(), Error> {
+        let time_frame = self
+            .times
+            .iter_mut()
+            .find(|time_frame| time_frame.id == edited.id)
+            .ok_or_else(|| Error::TimeFrameNotFound(edited.id.to_string()))?;
+        time_frame.subtask_id = edited.subtask_id;
+        time_frame.start_time = edited.start_time;
+        time_frame.end_time = edited.end_time;
+        self.times.sort_by_key(|time_frame| time_frame.start_time);
+
+        Ok(())
+    }
 
-fn render_create_popup<'a>(input: &'a str) -> Paragraph<'a> {
-    Paragraph::new(input.as_ref()).block(
-        Block::default()
-            .title("New project name")
-            .borders(Borders::ALL),
-    )
+    /// Sets the note of a recorded time frame, removing it if `note` is blank.
+    fn set_note(&mut self, id: Uuid, note: &str) -> Result<(), Error> {
+        let time_frame = self
+            .times
+            .iter_mut()
+            .find(|time_frame| time_frame.id == id)
+            .ok_or_else(|| Error::TimeFrameNotFound(id.to_string()))?;
+        let note = note.trim();
+        time_frame.note = (!note.is_empty()).then(|| note.to_owned());
+
+        Ok(())
+    }
+
+    fn delete_time_frame(&mut self, id: Uuid) -> Result<TimeFrame, Error> {
+        let index = self
+            .times
+            .iter()
+            .position(|time_frame| time_frame.id == id)
+            .ok_or_else(|| Error::TimeFrameNotFound(id.to_string()))?;
+
+        Ok(self.times.remove(index))
+    }
 }
 
-fn render_delete_project_popup<'a>() -> Paragraph<'a> {
-    Paragraph::new(Span::raw("y/n")).block(
-        Block::default()
-            .title("Confirm deletion")
-            .borders(Borders::ALL),
-    )
+/// Recorded time frames of any task that overlap `time_frame`, other than `time_frame` itself.
+fn find_overlaps<'a>(tasks: &'a [Task], time_frame: &TimeFrame) -> Vec<(&'a Task, &'a TimeFrame)> {
+    tasks
+        .iter()
+        .flat_map(|task| task.times.iter().map(move |other| (task, other)))
+        .filter(|(_, other)| other.id != time_frame.id && other.overlaps(time_frame))
+        .collect()
 }
 
-fn render_tasks<'a>() -> Table<'a> {
-    let task_list = read_db().expect("can fetch task list");
-    let rows: Vec<_> = task_list
+/// Recorded time frames of any task whose note contains `query`, ignoring case, in order of
+/// their start.
+fn search_notes<'a>(tasks: &'a [Task], query: &str) -> Vec<(&'a Task, &'a TimeFrame)> {
+    let query = query.to_lowercase();
+    let mut found: Vec<_> = tasks
         .iter()
-        .map(|task| {
-            Row::new(vec![
-                Cell::from(Span::raw(task.project.clone())),
-                Cell::from(Span::styled(
-                    {
-                        if task.is_running() {
-                            format!("Running [{}]", format_duration(task.current_duration()))
-                        } else {
-                            "Not running".to_owned()
-                        }
-                    },
-                    {
-                        if task.is_running() {
-                            Style::default().fg(Color::Green)
-                        } else {
-                            Style::default()
-                        }
-                    },
-                )),
-                Cell::from(Span::raw({
-                    let duration = task.total_duration();
-
-                    format_duration(duration)
-                })),
-            ])
+        .flat_map(|task| task.times.iter().map(move |time_frame| (task, time_frame)))
+        .filter(|(_, time_frame)| {
+            time_frame
+                .note
+                .as_ref()
+                .is_some_and(|note| note.to_lowercase().contains(&query))
         })
         .collect();
+    found.sort_by_key(|(_, time_frame)| time_frame.start_time);
 
-    let task_details = Table::new(rows)
-        .header(Row::new(vec![
-            Cell::from(Span::styled(
-                "Project",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Status",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-            Cell::from(Span::styled(
-                "Total",
-                Style::default().add_modifier(Modifier::BOLD),
-            )),
-        ]))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .style(Style::default().fg(Color::White))
-                .title("Details")
-                .border_type(BorderType::Plain),
-        )
-        .widths(&[
-            Constraint::Percentage(33),
-            Constraint::Percentage(33),
-            Constraint::Percentage(33),
-        ])
-        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)));
-
-    task_details
+    found
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, rect: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(
-            [
-                Constraint::Percentage((100 - percent_y) / 2),
-                Constraint::Percentage(percent_y),
-                Constraint::Percentage((100 - percent_y) / 2),
-            ]
-            .as_ref(),
-        )
-        .split(rect);
+/// Trims the name of a new or renamed project and checks that it is not empty and not used by
+/// another project than `task_id`, as projects are looked up by name.
+fn validate_project_name(
+    tasks: &[Task],
+    name: &str,
+    task_id: Option<Uuid>,
+) -> Result<String, Error> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::EmptyProjectName);
+    }
+    if tasks
+        .iter()
+        .any(|task| Some(task.id) != task_id && task.project == name)
+    {
+        return Err(Error::ProjectExists(name.to_owned()));
+    }
 
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(
-            [
-                Constraint::Percentage((100 - percent_x) / 2),
-                Constraint::Percentage(percent_x),
-                Constraint::Percentage((100 - percent_x) / 2),
-            ]
-            .as_ref(),
-        )
-        .split(popup_layout[1])[1]
+    Ok(name.to_owned())
+}
+
+/// Starts the timer of a subtask, stopping all others, or stops it if it is running. Returns
+/// the IDs of the task and time frame of a stopped timer.
+fn toggle_timer(
+    tasks: &mut [Task],
+    selected: usize,
+    subtask_id: Uuid,
+    merge_gap: Option<Duration>,
+) -> Option<(Uuid, Uuid)> {
+    let is_running = tasks[selected].is_running_subtask(subtask_id);
+
+    let mut stopped = None;
+    for task in tasks.iter_mut() {
+        if let Some(time_frame_id) = task.stop(merge_gap) {
+            stopped.get_or_insert((task.id, time_frame_id));
+        }
+    }
+
+    if !is_running {
+        tasks[selected].start(subtask_id);
+    }
+
+    stopped
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("error reading the DB file: {0}")]
+    ReadDBError(#[from] io::Error),
+    #[error("error parsing the DB file: {0}")]
+    ParseDBError(#[from] serde_json::Error),
+    #[error(
+        "the DB file has schema version {0}, but this version only supports up to {}",
+        migrations::SCHEMA_VERSION
+    )]
+    UnsupportedSchemaVersion(u32),
+    #[error("the DB is locked by another process ({})", .0.display())]
+    DBLocked(PathBuf),
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[error("cannot migrate into {}, it already contains data", .0.display())]
+    StorageNotEmpty(PathBuf),
+    #[error("the data read back from {} differs from the source", .0.display())]
+    StorageMismatch(PathBuf),
+    #[error("error reading the config file: {0}")]
+    ReadConfigError(io::Error),
+    #[error("error parsing the config file: {0}")]
+    ParseConfigError(#[from] toml::de::Error),
+    #[error("invalid timestamp \"{0}\", expected YYYY-MM-DD HH:MM")]
+    InvalidTimestamp(String),
+    #[error("\"{0}\" does not exist in the configured time zone, it is skipped by a DST change")]
+    NonexistentTimestamp(String),
+    #[error("the end time must be after the start time")]
+    InvalidTimeFrame,
+    #[error("invalid date \"{0}\", expected YYYY-MM-DD")]
+    InvalidDate(String),
+    #[error("invalid month \"{0}\", expected YYYY-MM")]
+    InvalidMonth(String),
+    #[error("the last day must not be before the first day")]
+    InvalidPeriod,
+    #[error("error writing the report: {0}")]
+    WriteReportError(io::Error),
+    #[error("invalid report name template \"{0}\"")]
+    InvalidReportName(String),
+    #[error("no time frame with ID \"{0}\"")]
+    TimeFrameNotFound(String),
+    #[error("more than one time frame ID starts with \"{0}\"")]
+    AmbiguousTimeFrameId(String),
+    #[error("no project named \"{0}\"")]
+    ProjectNotFound(String),
+    #[error("a project named \"{0}\" already exists")]
+    ProjectExists(String),
+    #[error("the project name must not be empty")]
+    EmptyProjectName,
+    #[error("project \"{0}\" has no task named \"{1}\"")]
+    SubtaskNotFound(String, String),
+    #[error("project \"{0}\" already has a task named \"{1}\"")]
+    SubtaskExists(String, String),
+    #[error("the task name must not be empty")]
+    EmptySubtaskName,
+    #[error("the last task of a project cannot be deleted")]
+    LastSubtask,
+    #[error("the idle command failed: {0}")]
+    IdleCommandError(String),
+    #[error("the timer of \"{0}\" was started after that time")]
+    StopBeforeStart(String),
+    #[error("the timer of \"{0}\" was started at that time, so no time would be recorded")]
+    StopAtStart(String),
+    #[error("no activity was noticed while the timer of \"{0}\" ran; stop it at a time instead")]
+    NoActivityRecorded(String),
+    #[error("the stop time must not be in the future")]
+    StopInFuture,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = cli::Cli::parse();
+    let config = Config::load()?;
+    let db_path = config.db_path(args.db.as_deref());
+    let storage = storage::open(config.storage, &db_path)?;
+
+    // Only purge along with changes, so that reading the data never writes the storage
+    let writes = args.command.as_ref().is_none_or(cli::Command::writes);
+    if let (true, Some(retention)) = (writes, config.trash.retention()) {
+        trash::purge_expired(storage.as_ref(), retention)?;
+    }
+
+    match args.command {
+        Some(command) => cli::run(command, storage, &config),
+        None => ui::run(storage, config),
+    }
 }
 
 fn format_duration(duration: Duration) -> String {
@@ -554,64 +701,266 @@ fn format_duration(duration: Duration) -> String {
     format!("{:0>2}:{:0>2}:{:0>2}", hours, minutes, seconds)
 }
 
-fn format_duration_report(duration: Duration) -> String {
-    let total_minutes = (((duration.num_seconds() as f64) / 60.0 / 15.0).ceil() * 15.0) as i64;
-    let minutes = total_minutes % 60;
-    let hours = (total_minutes / 60) / 60;
-
-    format!("{:0>2}:{:0>2}", hours, minutes)
+fn format_timestamp(timestamp: DateTime<Utc>, time_zone: Tz) -> String {
+    timestamp
+        .with_timezone(&time_zone)
+        .format(TIMESTAMP_FORMAT)
+        .to_string()
 }
 
-fn read_db() -> Result<Vec<Task>, Error> {
-    let mut db_content = String::new();
-    match OpenOptions::new().read(true).open(DB_PATH) {
-        Ok(mut file) => {
-            file.read_to_string(&mut db_content)?;
-        }
-        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
-            db_content.push_str("[]");
-        }
-        Err(e) => {
-            return Err(Error::ReadDBError(e));
-        }
-    };
+/// Parses `YYYY-MM-DD HH:MM`, optionally with seconds, in `time_zone` or an RFC 3339
+/// timestamp. Local times that occur twice because of a DST change refer to the first.
+fn parse_timestamp(input: &str, time_zone: Tz) -> Result<DateTime<Utc>, Error> {
+    let input = input.trim();
 
-    let parsed: Vec<Task> = serde_json::from_str(&db_content)?;
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
 
-    Ok(parsed)
+    let timestamp = ["%Y-%m-%d %H:%M:%S", TIMESTAMP_FORMAT]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .ok_or_else(|| Error::InvalidTimestamp(input.to_owned()))?;
+
+    time_zone
+        .from_local_datetime(&timestamp)
+        .earliest()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .ok_or_else(|| Error::NonexistentTimestamp(input.to_owned()))
 }
 
-fn update_db(updater: impl Fn(&mut Vec<Task>) -> ()) -> Result<(), Error> {
-    // Ensure path exists
-    let db_path: PathBuf = DB_PATH.into();
-    let db_dir = db_path.parent().unwrap_or("./".as_ref());
-    fs::create_dir_all(db_dir)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{time_frame, utc};
 
-    // Open file for reading and writing - ensure that file is created if does not exist
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .append(false)
-        .open(DB_PATH)?;
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        utc(2024, 5, 2, hour, minute)
+    }
 
-    // Read and parse file
-    let mut db_content = String::new();
+    fn ranges(groups: &[Vec<&TimeFrame>]) -> Vec<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+        groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|time_frame| (time_frame.start_time, time_frame.end_time))
+                    .collect()
+            })
+            .collect()
+    }
 
-    file.read_to_string(&mut db_content)?;
+    #[test]
+    fn groups_time_frames_up_to_the_gap_apart() {
+        let subtask = Uuid::new_v4();
+        let times = [
+            time_frame(subtask, at(9, 0), at(10, 0)),
+            // Exactly the gap after the previous one
+            time_frame(subtask, at(10, 15), at(11, 0)),
+            // One minute more
+            time_frame(subtask, at(11, 16), at(12, 0)),
+        ];
+
+        let groups = group_adjacent(&times, Duration::minutes(15));
+
+        assert_eq!(
+            ranges(&groups),
+            [
+                vec![(at(9, 0), at(10, 0)), (at(10, 15), at(11, 0))],
+                vec![(at(11, 16), at(12, 0))],
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_unsorted_time_frames_by_start_time() {
+        let subtask = Uuid::new_v4();
+        let times = [
+            time_frame(subtask, at(13, 0), at(14, 0)),
+            time_frame(subtask, at(9, 0), at(10, 0)),
+            time_frame(subtask, at(10, 5), at(11, 0)),
+        ];
 
-    if db_content.is_empty() {
-        db_content.push_str("[]");
+        let groups = group_adjacent(&times, Duration::minutes(15));
+
+        assert_eq!(
+            ranges(&groups),
+            [
+                vec![(at(9, 0), at(10, 0)), (at(10, 5), at(11, 0))],
+                vec![(at(13, 0), at(14, 0))],
+            ]
+        );
     }
 
-    let mut parsed: Vec<Task> = serde_json::from_str(&db_content)?;
+    #[test]
+    fn groups_overlapping_time_frames_from_the_latest_end() {
+        let subtask = Uuid::new_v4();
+        let times = [
+            time_frame(subtask, at(9, 0), at(12, 0)),
+            // Contained in the first one, so its earlier end does not shorten the group
+            time_frame(subtask, at(10, 0), at(10, 30)),
+            time_frame(subtask, at(12, 10), at(13, 0)),
+        ];
+
+        let groups = group_adjacent(&times, Duration::minutes(15));
+        assert_eq!(groups.len(), 1);
+
+        let merged = merge_group(&groups[0]);
+        assert_eq!(merged.id, times[0].id);
+        assert_eq!((merged.start_time, merged.end_time), (at(9, 0), at(13, 0)));
+    }
+
+    #[test]
+    fn groups_subtasks_separately() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let times = [
+            time_frame(first, at(9, 0), at(10, 0)),
+            time_frame(second, at(10, 0), at(11, 0)),
+            time_frame(first, at(11, 0), at(12, 0)),
+        ];
 
-    // Update data
-    updater(&mut parsed);
+        let groups = group_adjacent(&times, Duration::minutes(15));
 
-    // Write back to disk
-    let serialized = &serde_json::to_vec(&parsed)?;
-    fs::write(DB_PATH, serialized)?;
+        assert_eq!(
+            ranges(&groups),
+            [
+                vec![(at(9, 0), at(10, 0))],
+                vec![(at(10, 0), at(11, 0))],
+                vec![(at(11, 0), at(12, 0))],
+            ]
+        );
+    }
 
-    Ok(())
+    #[test]
+    fn joins_distinct_notes_of_merged_time_frames() {
+        let subtask = Uuid::new_v4();
+        let mut times = [
+            time_frame(subtask, at(9, 0), at(10, 0)),
+            time_frame(subtask, at(10, 0), at(11, 0)),
+            time_frame(subtask, at(11, 0), at(12, 0)),
+            time_frame(subtask, at(12, 0), at(13, 0)),
+        ];
+        times[0].note = Some("Design".to_owned());
+        times[2].note = Some("Review".to_owned());
+        times[3].note = Some("Design".to_owned());
+
+        let group: Vec<_> = times.iter().collect();
+
+        assert_eq!(merge_group(&group).note.as_deref(), Some("Design; Review"));
+    }
+
+    #[test]
+    fn extends_the_previous_time_frame_when_stopped_within_the_gap() {
+        let mut task = Task::new("Website".to_owned());
+        let subtask = task.subtasks[0].id;
+        task.times.push(time_frame(subtask, at(9, 0), at(10, 0)));
+        task.running_since = Some(at(10, 15));
+        task.running_subtask = Some(subtask);
+
+        let id = task.stop_at(at(11, 0), Some(Duration::minutes(15)));
+
+        assert_eq!(id, Some(task.times[0].id));
+        assert_eq!(task.times.len(), 1);
+        assert_eq!(task.times[0].end_time, at(11, 0));
+        assert!(!task.is_running());
+    }
+
+    #[test]
+    fn adds_a_time_frame_when_stopped_after_the_gap() {
+        let mut task = Task::new("Website".to_owned());
+        let subtask = task.subtasks[0].id;
+        task.times.push(time_frame(subtask, at(9, 0), at(10, 0)));
+        task.running_since = Some(at(10, 16));
+        task.running_subtask = Some(subtask);
+
+        let id = task.stop_at(at(11, 0), Some(Duration::minutes(15)));
+
+        assert_eq!(task.times.len(), 2);
+        assert_eq!(id, Some(task.times[1].id));
+        assert_eq!(
+            (task.times[1].start_time, task.times[1].end_time),
+            (at(10, 16), at(11, 0))
+        );
+    }
+
+    #[test]
+    fn does_not_stop_a_timer_that_is_not_running() {
+        let mut task = Task::new("Website".to_owned());
+
+        assert_eq!(task.stop_at(at(11, 0), Some(Duration::minutes(15))), None);
+        assert!(task.times.is_empty());
+    }
+
+    fn ranges_of(times: &[TimeFrame]) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        times
+            .iter()
+            .map(|time_frame| (time_frame.start_time, time_frame.end_time))
+            .collect()
+    }
+
+    #[test]
+    fn does_not_merge_discarded_idle_time_back() {
+        let gap = Some(Duration::minutes(30));
+        let mut task = Task::new("Website".to_owned());
+        task.start(task.subtasks[0].id);
+        task.running_since = Some(at(9, 0));
+
+        task.remove_idle_time(at(10, 0), at(10, 20), false, gap);
+        let id = task.stop_at(at(11, 0), gap);
+
+        assert_eq!(id, Some(task.times[1].id));
+        assert_eq!(
+            ranges_of(&task.times),
+            [(at(9, 0), at(10, 0)), (at(10, 20), at(11, 0))]
+        );
+        assert_eq!(
+            ranges_of(&task.merged_times(Duration::minutes(30))),
+            ranges_of(&task.times)
+        );
+    }
+
+    #[test]
+    fn does_not_merge_into_split_idle_time() {
+        let gap = Some(Duration::minutes(30));
+        let mut task = Task::new("Website".to_owned());
+        task.start(task.subtasks[0].id);
+        task.running_since = Some(at(9, 0));
+
+        task.remove_idle_time(at(10, 0), at(10, 20), true, gap);
+        let id = task.stop_at(at(11, 0), gap);
+
+        assert_eq!(id, Some(task.times[2].id));
+        assert_eq!(task.times[1].note.as_deref(), Some(IDLE_NOTE));
+        assert_eq!(
+            ranges_of(&task.times),
+            [
+                (at(9, 0), at(10, 0)),
+                (at(10, 0), at(10, 20)),
+                (at(10, 20), at(11, 0)),
+            ]
+        );
+        assert_eq!(
+            ranges_of(&task.merged_times(Duration::minutes(30))),
+            ranges_of(&task.times)
+        );
+    }
+
+    #[test]
+    fn merges_later_time_frames_after_idle_time() {
+        let gap = Some(Duration::minutes(30));
+        let mut task = Task::new("Website".to_owned());
+        let subtask = task.subtasks[0].id;
+        task.start(subtask);
+        task.running_since = Some(at(9, 0));
+        task.remove_idle_time(at(10, 0), at(10, 20), true, gap);
+        task.stop_at(at(11, 0), gap);
+
+        task.start(subtask);
+        task.running_since = Some(at(11, 10));
+        let id = task.stop_at(at(12, 0), gap);
+
+        assert_eq!(id, Some(task.times[2].id));
+        assert_eq!(task.times[2].end_time, at(12, 0));
+        assert_eq!(task.times[1].end_time, at(10, 20));
+    }
 }
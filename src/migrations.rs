@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{Error, DEFAULT_SUBTASK};
+
+/// Schema version written by this build.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// `MIGRATIONS[n]` upgrades a DB from schema version `n` to `n + 1`.
+const MIGRATIONS: [fn(Value) -> Value; SCHEMA_VERSION as usize] =
+    [migrate_v0_to_v1, migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Upgrades a parsed DB file to `SCHEMA_VERSION`. Returns the upgraded content and, if any
+/// migration ran, the version the file was written with.
+pub fn upgrade(mut db: Value) -> Result<(Value, Option<u32>), Error> {
+    let version = schema_version(&db);
+    if version > SCHEMA_VERSION {
+        return Err(Error::UnsupportedSchemaVersion(version));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        db = migration(db);
+    }
+
+    Ok((db, (version < SCHEMA_VERSION).then_some(version)))
+}
+
+/// Files written before the envelope was introduced are a bare array of tasks (version 0).
+fn schema_version(db: &Value) -> u32 {
+    match db.get("schema_version").and_then(Value::as_u64) {
+        Some(version) => version.try_into().unwrap_or(u32::MAX),
+        None => 0,
+    }
+}
+
+/// Wraps the task array in the versioned envelope and replaces the sequential integer IDs of
+/// tasks and time frames, as well as any duplicates, with UUIDs.
+fn migrate_v0_to_v1(mut tasks: Value) -> Value {
+    let mut seen = HashSet::new();
+
+    for task in tasks.as_array_mut().into_iter().flatten() {
+        let times = task
+            .get_mut("times")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for time_frame in times {
+            reassign_id(time_frame, &mut seen);
+        }
+
+        reassign_id(task, &mut seen);
+    }
+
+    json!({ "schema_version": 1, "tasks": tasks })
+}
+
+/// Adds the trash of deleted projects, which starts out empty.
+fn migrate_v1_to_v2(mut db: Value) -> Value {
+    if let Some(db) = db.as_object_mut() {
+        db.entry("trash").or_insert_with(|| json!([]));
+        db.insert("schema_version".to_owned(), json!(2));
+    }
+
+    db
+}
+
+/// Splits projects into subtasks by giving every project, including those in the trash, a
+/// default subtask that all of its time frames and its running timer belong to.
+fn migrate_v2_to_v3(mut db: Value) -> Value {
+    if let Some(db) = db.as_object_mut() {
+        let tasks = db
+            .get_mut("tasks")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for task in tasks {
+            add_default_subtask(task);
+        }
+
+        let trash = db
+            .get_mut("trash")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for trashed in trash {
+            if let Some(task) = trashed.get_mut("task") {
+                add_default_subtask(task);
+            }
+        }
+
+        db.insert("schema_version".to_owned(), json!(3));
+    }
+
+    db
+}
+
+fn add_default_subtask(task: &mut Value) {
+    let id = Uuid::new_v4();
+    let is_running = task
+        .get("running_since")
+        .is_some_and(|since| !since.is_null());
+
+    let times = task
+        .get_mut("times")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for time_frame in times {
+        if let Some(time_frame) = time_frame.as_object_mut() {
+            time_frame.insert("subtask_id".to_owned(), json!(id));
+        }
+    }
+
+    if let Some(task) = task.as_object_mut() {
+        let created_at = task.get("created_at").cloned().unwrap_or(Value::Null);
+        task.insert(
+            "subtasks".to_owned(),
+            json!([{ "id": id, "name": DEFAULT_SUBTASK, "created_at": created_at }]),
+        );
+        task.insert(
+            "running_subtask".to_owned(),
+            if is_running { json!(id) } else { Value::Null },
+        );
+    }
+}
+
+fn reassign_id(entry: &mut Value, seen: &mut HashSet<Uuid>) {
+    let id = entry
+        .get("id")
+        .and_then(Value::as_str)
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .filter(|id| seen.insert(*id));
+
+    if let (None, Some(entry)) = (id, entry.as_object_mut()) {
+        let id = Uuid::new_v4();
+        seen.insert(id);
+        entry.insert("id".to_owned(), json!(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Data;
+
+    /// Used by a time frame and, again, by a later task
+    const DUPLICATE_ID: &str = "6f1c2d1e-54a3-4c8b-9a55-3d1f0c6a7b21";
+
+    fn v0_file() -> Value {
+        json!([
+            {
+                "id": 1,
+                "project": "Website",
+                "created_at": "2024-04-30T08:00:00Z",
+                "running_since": "2024-05-02T09:00:00Z",
+                "times": [
+                    { "id": 1, "start_time": "2024-05-01T08:00:00Z", "end_time": "2024-05-01T09:00:00Z" },
+                    { "id": DUPLICATE_ID, "start_time": "2024-05-01T10:00:00Z", "end_time": "2024-05-01T11:00:00Z" },
+                ],
+            },
+            {
+                "id": DUPLICATE_ID,
+                "project": "Mobile app",
+                "created_at": "2024-04-30T08:00:00Z",
+                "running_since": null,
+                "times": [
+                    { "start_time": "2024-05-01T12:00:00Z", "end_time": "2024-05-01T13:00:00Z" },
+                ],
+            },
+        ])
+    }
+
+    fn ids(db: &Value) -> Vec<Uuid> {
+        db["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|task| std::iter::once(task).chain(task["times"].as_array().unwrap()))
+            .map(|entry| Uuid::parse_str(entry["id"].as_str().unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn replaces_missing_integer_and_duplicate_ids() {
+        let (db, _) = upgrade(v0_file()).unwrap();
+        let ids = ids(&db);
+
+        assert_eq!(ids.len(), 5);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+        // The first occurrence of a valid UUID is kept
+        assert_eq!(db["tasks"][0]["times"][1]["id"], DUPLICATE_ID);
+        assert_ne!(db["tasks"][1]["id"], DUPLICATE_ID);
+    }
+
+    #[test]
+    fn chains_migrations_from_version_0() {
+        let (db, migrated_from) = upgrade(v0_file()).unwrap();
+
+        assert_eq!(migrated_from, Some(0));
+        assert_eq!(db["schema_version"], SCHEMA_VERSION);
+        assert_eq!(db["trash"], json!([]));
+
+        let website = &db["tasks"][0];
+        let subtask_id = &website["subtasks"][0]["id"];
+        assert_eq!(website["subtasks"][0]["name"], DEFAULT_SUBTASK);
+        assert_eq!(&website["running_subtask"], subtask_id);
+        assert_eq!(&website["times"][0]["subtask_id"], subtask_id);
+        assert_eq!(db["tasks"][1]["running_subtask"], Value::Null);
+
+        let data: Data = serde_json::from_value(db).unwrap();
+        assert_eq!(data.tasks.len(), 2);
+    }
+
+    #[test]
+    fn leaves_current_files_unchanged() {
+        let (db, _) = upgrade(v0_file()).unwrap();
+        let (upgraded, migrated_from) = upgrade(db.clone()).unwrap();
+
+        assert_eq!(migrated_from, None);
+        assert_eq!(upgraded, db);
+    }
+
+    #[test]
+    fn rejects_newer_schema_versions() {
+        let db = json!({ "schema_version": SCHEMA_VERSION + 1, "tasks": [] });
+
+        assert!(matches!(
+            upgrade(db),
+            Err(Error::UnsupportedSchemaVersion(version)) if version == SCHEMA_VERSION + 1
+        ));
+    }
+}
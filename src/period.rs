@@ -0,0 +1,331 @@
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+
+use crate::{time_zone::start_of_day, Error, TimeFrame};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Range of whole days a report covers, including both ends.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Period {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+}
+
+/// Commonly reported periods, relative to the current day. Weeks start on Monday.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preset {
+    Today,
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+}
+
+impl Period {
+    pub fn new(first_day: NaiveDate, last_day: NaiveDate) -> Result<Self, Error> {
+        if last_day < first_day {
+            return Err(Error::InvalidPeriod);
+        }
+
+        Ok(Period {
+            first_day,
+            last_day,
+        })
+    }
+
+    /// The calendar month containing `day`.
+    pub fn month(day: NaiveDate) -> Self {
+        let first_day = day.with_day(1).expect("every month has a first day");
+        let next_month = if first_day.month() == 12 {
+            NaiveDate::from_ymd_opt(first_day.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(first_day.year(), first_day.month() + 1, 1)
+        }
+        .expect("the next month is a valid date");
+
+        Period {
+            first_day,
+            last_day: next_month.pred_opt().expect("is after the first day"),
+        }
+    }
+
+    /// The week, from Monday to Sunday, containing `day`.
+    pub fn week(day: NaiveDate) -> Self {
+        let first_day = day - Duration::days(day.weekday().num_days_from_monday().into());
+
+        Period {
+            first_day,
+            last_day: first_day + Duration::days(6),
+        }
+    }
+
+    pub fn start(&self, time_zone: Tz) -> DateTime<Utc> {
+        start_of_day(self.first_day, time_zone)
+    }
+
+    /// Start of the day after the period.
+    pub fn end(&self, time_zone: Tz) -> DateTime<Utc> {
+        start_of_day(self.last_day + Duration::days(1), time_zone)
+    }
+
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        let last_day = self.last_day;
+        self.first_day
+            .iter_days()
+            .take_while(move |day| *day <= last_day)
+    }
+
+    /// Whether the period is exactly one calendar month.
+    pub fn is_month(&self) -> bool {
+        *self == Period::month(self.first_day)
+    }
+
+    /// The period of the same kind, week, month or number of days, that follows this one.
+    pub fn next(&self) -> Self {
+        if self.is_month() {
+            Period::month(self.last_day + Duration::days(1))
+        } else {
+            self.shift(self.last_day - self.first_day + Duration::days(1))
+        }
+    }
+
+    /// The period of the same kind, week, month or number of days, that precedes this one.
+    pub fn previous(&self) -> Self {
+        if self.is_month() {
+            Period::month(self.first_day - Duration::days(1))
+        } else {
+            self.shift(self.first_day - self.last_day - Duration::days(1))
+        }
+    }
+
+    fn shift(&self, offset: Duration) -> Self {
+        Period {
+            first_day: self.first_day + offset,
+            last_day: self.last_day + offset,
+        }
+    }
+
+    /// The part of `time_frame` that lies within the period's days in `time_zone`, if any.
+    pub fn clip(&self, time_frame: &TimeFrame, time_zone: Tz) -> Option<TimeFrame> {
+        let start_time = time_frame.start_time.max(self.start(time_zone));
+        let end_time = time_frame.end_time.min(self.end(time_zone));
+
+        (start_time < end_time).then_some(TimeFrame {
+            id: time_frame.id,
+            subtask_id: time_frame.subtask_id,
+            start_time,
+            end_time,
+            note: time_frame.note.clone(),
+            after_idle: time_frame.after_idle,
+        })
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.first_day == self.last_day {
+            write!(f, "{}", self.first_day.format(DATE_FORMAT))
+        } else {
+            write!(
+                f,
+                "{} to {}",
+                self.first_day.format(DATE_FORMAT),
+                self.last_day.format(DATE_FORMAT)
+            )
+        }
+    }
+}
+
+impl Preset {
+    pub fn period(self, today: NaiveDate) -> Period {
+        match self {
+            Preset::Today => Period {
+                first_day: today,
+                last_day: today,
+            },
+            Preset::ThisWeek => Period::week(today),
+            Preset::LastWeek => Period::week(today - Duration::days(7)),
+            Preset::ThisMonth => Period::month(today),
+            Preset::LastMonth => Period::month(Period::month(today).first_day - Duration::days(1)),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::Today => "Today",
+            Preset::ThisWeek => "This week",
+            Preset::LastWeek => "Last week",
+            Preset::ThisMonth => "This month",
+            Preset::LastMonth => "Last month",
+        }
+    }
+}
+
+/// Parses a date as `YYYY-MM-DD`.
+pub fn parse_date(input: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(input.trim(), DATE_FORMAT)
+        .map_err(|_| Error::InvalidDate(input.trim().to_owned()))
+}
+
+/// Parses a month as `YYYY-MM`.
+pub fn parse_month(input: &str) -> Result<Period, Error> {
+    NaiveDate::parse_from_str(&format!("{}-01", input.trim()), DATE_FORMAT)
+        .map(Period::month)
+        .map_err(|_| Error::InvalidMonth(input.trim().to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::test_support::{day, time_frame, utc};
+
+    fn clipped(
+        period: Period,
+        time_frame: &TimeFrame,
+        time_zone: Tz,
+    ) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        period
+            .clip(time_frame, time_zone)
+            .map(|time_frame| (time_frame.start_time, time_frame.end_time))
+    }
+
+    #[test]
+    fn clips_time_frames_crossing_midnight() {
+        let period = Period::new(day(2024, 5, 2), day(2024, 5, 3)).unwrap();
+
+        let into_first_day = time_frame(Uuid::nil(), utc(2024, 5, 1, 22, 0), utc(2024, 5, 2, 2, 0));
+        assert_eq!(
+            clipped(period, &into_first_day, Tz::UTC),
+            Some((utc(2024, 5, 2, 0, 0), utc(2024, 5, 2, 2, 0)))
+        );
+
+        let past_last_day = time_frame(Uuid::nil(), utc(2024, 5, 3, 23, 0), utc(2024, 5, 4, 1, 0));
+        assert_eq!(
+            clipped(period, &past_last_day, Tz::UTC),
+            Some((utc(2024, 5, 3, 23, 0), utc(2024, 5, 4, 0, 0)))
+        );
+
+        let around = time_frame(Uuid::nil(), utc(2024, 5, 1, 12, 0), utc(2024, 5, 5, 12, 0));
+        assert_eq!(
+            clipped(period, &around, Tz::UTC),
+            Some((utc(2024, 5, 2, 0, 0), utc(2024, 5, 4, 0, 0)))
+        );
+    }
+
+    #[test]
+    fn keeps_the_id_and_note_of_clipped_time_frames() {
+        let period = Period::new(day(2024, 5, 2), day(2024, 5, 2)).unwrap();
+        let mut time_frame = time_frame(Uuid::nil(), utc(2024, 5, 1, 22, 0), utc(2024, 5, 2, 2, 0));
+        time_frame.note = Some("Design".to_owned());
+
+        let clipped = period.clip(&time_frame, Tz::UTC).unwrap();
+
+        assert_eq!(clipped.id, time_frame.id);
+        assert_eq!(clipped.note, time_frame.note);
+    }
+
+    #[test]
+    fn drops_time_frames_outside_or_ending_at_the_boundaries() {
+        let period = Period::new(day(2024, 5, 2), day(2024, 5, 2)).unwrap();
+
+        let before = time_frame(Uuid::nil(), utc(2024, 5, 1, 22, 0), utc(2024, 5, 2, 0, 0));
+        let after = time_frame(Uuid::nil(), utc(2024, 5, 3, 0, 0), utc(2024, 5, 3, 2, 0));
+
+        assert_eq!(clipped(period, &before, Tz::UTC), None);
+        assert_eq!(clipped(period, &after, Tz::UTC), None);
+    }
+
+    #[test]
+    fn clips_at_local_midnight() {
+        let period = Period::new(day(2024, 5, 2), day(2024, 5, 2)).unwrap();
+        // 23:00 to 01:00 in Berlin, which is two hours ahead of UTC in summer
+        let time_frame = time_frame(Uuid::nil(), utc(2024, 5, 1, 21, 0), utc(2024, 5, 1, 23, 0));
+
+        assert_eq!(
+            clipped(period, &time_frame, Tz::Europe__Berlin),
+            Some((utc(2024, 5, 1, 22, 0), utc(2024, 5, 1, 23, 0)))
+        );
+        assert_eq!(clipped(period, &time_frame, Tz::UTC), None);
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // A Sunday, the last day of its week
+        let sunday = day(2024, 5, 5);
+
+        assert_eq!(
+            Preset::ThisWeek.period(sunday),
+            Period::new(day(2024, 4, 29), day(2024, 5, 5)).unwrap()
+        );
+        assert_eq!(
+            Preset::ThisWeek.period(day(2024, 5, 6)),
+            Period::new(day(2024, 5, 6), day(2024, 5, 12)).unwrap()
+        );
+        assert_eq!(
+            Preset::LastWeek.period(sunday),
+            Period::new(day(2024, 4, 22), day(2024, 4, 28)).unwrap()
+        );
+    }
+
+    #[test]
+    fn weeks_cross_the_end_of_the_year() {
+        assert_eq!(
+            Preset::ThisWeek.period(day(2025, 1, 1)),
+            Period::new(day(2024, 12, 30), day(2025, 1, 5)).unwrap()
+        );
+    }
+
+    #[test]
+    fn months_cover_all_their_days() {
+        assert_eq!(
+            Preset::ThisMonth.period(day(2024, 2, 29)),
+            Period::new(day(2024, 2, 1), day(2024, 2, 29)).unwrap()
+        );
+        assert_eq!(
+            Preset::ThisMonth.period(day(2023, 2, 1)),
+            Period::new(day(2023, 2, 1), day(2023, 2, 28)).unwrap()
+        );
+        assert_eq!(
+            Preset::ThisMonth.period(day(2024, 12, 31)),
+            Period::new(day(2024, 12, 1), day(2024, 12, 31)).unwrap()
+        );
+    }
+
+    #[test]
+    fn last_month_crosses_the_end_of_the_year() {
+        assert_eq!(
+            Preset::LastMonth.period(day(2025, 1, 1)),
+            Period::new(day(2024, 12, 1), day(2024, 12, 31)).unwrap()
+        );
+        // Shorter than the current month
+        assert_eq!(
+            Preset::LastMonth.period(day(2024, 3, 31)),
+            Period::new(day(2024, 2, 1), day(2024, 2, 29)).unwrap()
+        );
+    }
+
+    #[test]
+    fn steps_through_months_and_weeks() {
+        let january = Period::month(day(2024, 1, 10));
+        assert_eq!(january.next(), Period::month(day(2024, 2, 1)));
+        assert_eq!(january.previous(), Period::month(day(2023, 12, 1)));
+
+        let week = Period::week(day(2024, 12, 31));
+        assert_eq!(week.next(), Period::week(day(2025, 1, 6)));
+        assert_eq!(week.previous(), Period::week(day(2024, 12, 23)));
+    }
+
+    #[test]
+    fn rejects_periods_ending_before_they_start() {
+        assert!(matches!(
+            Period::new(day(2024, 5, 2), day(2024, 5, 1)),
+            Err(Error::InvalidPeriod)
+        ));
+    }
+}
@@ -0,0 +1,619 @@
+use chrono::{DateTime, Duration};
+use chrono_tz::Tz;
+use serde_json::json;
+
+use crate::{
+    details::Details,
+    report::{format_duration_report, Summary},
+    timesheet::Timesheet,
+    TIMESTAMP_FORMAT,
+};
+
+/// Column header of a day in timesheets, except for CSV which uses ISO dates.
+pub const TIMESHEET_DAY_FORMAT: &str = "%a %m-%d";
+
+/// Turns reports into the content of an output file.
+pub trait Renderer {
+    fn render_summary(&self, summary: &Summary) -> String;
+
+    fn render_timesheet(&self, timesheet: &Timesheet) -> String;
+
+    fn render_details(&self, details: &Details) -> String;
+}
+
+/// Comma separated values, quoted as described in RFC 4180.
+pub struct Csv;
+
+pub struct Json;
+
+/// A Markdown document with the report as a table.
+pub struct Markdown;
+
+/// A standalone HTML page without external resources.
+pub struct Html;
+
+impl Renderer for Csv {
+    fn render_summary(&self, report: &Summary) -> String {
+        let mut csv = String::new();
+
+        push_csv_row(
+            &mut csv,
+            &[
+                report.group_by.label(),
+                &format!("Duration {}", report.description()),
+            ],
+        );
+        for row in &report.rows {
+            push_csv_row(
+                &mut csv,
+                &[&row.name, &format_duration_report(row.duration)],
+            );
+        }
+
+        csv
+    }
+
+    fn render_timesheet(&self, timesheet: &Timesheet) -> String {
+        let mut csv = String::new();
+
+        let mut header = vec![format!("Project {}", timesheet.description())];
+        header.extend(timesheet.days.iter().map(ToString::to_string));
+        header.push("Total".to_owned());
+        push_csv_row(&mut csv, &header);
+
+        for row in &timesheet.rows {
+            let mut fields = vec![row.project.clone()];
+            fields.extend(
+                row.days
+                    .iter()
+                    .map(|duration| format_duration_report(*duration)),
+            );
+            fields.push(format_duration_report(row.total()));
+            push_csv_row(&mut csv, &fields);
+        }
+
+        let mut totals = vec!["Total".to_owned()];
+        totals.extend(
+            timesheet
+                .day_totals()
+                .into_iter()
+                .map(format_duration_report),
+        );
+        totals.push(format_duration_report(timesheet.total()));
+        push_csv_row(&mut csv, &totals);
+
+        csv
+    }
+
+    fn render_details(&self, details: &Details) -> String {
+        let mut csv = String::new();
+
+        push_csv_row(
+            &mut csv,
+            &[
+                &format!("Project {}", details.description()),
+                "Task",
+                "Start",
+                "End",
+                "Duration",
+                "Note",
+            ],
+        );
+        for entry in &details.entries {
+            push_csv_row(
+                &mut csv,
+                &[
+                    entry.project.clone(),
+                    entry.task.clone(),
+                    entry.start_time.format(TIMESTAMP_FORMAT).to_string(),
+                    entry.end_time.format(TIMESTAMP_FORMAT).to_string(),
+                    format_duration_report(entry.duration()),
+                    entry.note.clone().unwrap_or_default(),
+                ],
+            );
+        }
+        push_csv_row(
+            &mut csv,
+            &[
+                "Total",
+                "",
+                "",
+                "",
+                &format_duration_report(details.total()),
+                "",
+            ],
+        );
+
+        csv
+    }
+}
+
+impl Renderer for Json {
+    fn render_summary(&self, report: &Summary) -> String {
+        let key = report.group_by.label().to_lowercase();
+        let rows: Vec<_> = report
+            .rows
+            .iter()
+            .map(|row| {
+                json!({
+                    &key: row.name,
+                    "duration": format_duration_report(row.duration),
+                    "minutes": row.duration.num_minutes(),
+                })
+            })
+            .collect();
+
+        let period = report.period.map(|period| {
+            json!({
+                "first_day": period.first_day,
+                "last_day": period.last_day,
+            })
+        });
+
+        let report = json!({
+            "generated_at": report.generated_at,
+            "period": period,
+            "rounding": report.rounding.to_string(),
+            format!("{}s", key): rows,
+            "total": format_duration_report(report.total),
+            "total_minutes": report.total.num_minutes(),
+        });
+
+        to_json(&report)
+    }
+
+    fn render_timesheet(&self, timesheet: &Timesheet) -> String {
+        let day_entries = |durations: &[Duration]| -> Vec<_> {
+            timesheet
+                .days
+                .iter()
+                .zip(durations)
+                .map(|(day, duration)| {
+                    json!({
+                        "day": day,
+                        "duration": format_duration_report(*duration),
+                        "minutes": duration.num_minutes(),
+                    })
+                })
+                .collect()
+        };
+
+        let projects: Vec<_> = timesheet
+            .rows
+            .iter()
+            .map(|row| {
+                json!({
+                    "project": row.project,
+                    "days": day_entries(&row.days),
+                    "total": format_duration_report(row.total()),
+                    "total_minutes": row.total().num_minutes(),
+                })
+            })
+            .collect();
+
+        to_json(&json!({
+            "generated_at": timesheet.generated_at,
+            "period": {
+                "first_day": timesheet.period.first_day,
+                "last_day": timesheet.period.last_day,
+            },
+            "rounding": timesheet.rounding.to_string(),
+            "projects": projects,
+            "days": day_entries(&timesheet.day_totals()),
+            "total": format_duration_report(timesheet.total()),
+            "total_minutes": timesheet.total().num_minutes(),
+        }))
+    }
+
+    fn render_details(&self, details: &Details) -> String {
+        let time_frames: Vec<_> = details
+            .entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "project": entry.project,
+                    "task": entry.task,
+                    "start_time": entry.start_time.to_rfc3339(),
+                    "end_time": entry.end_time.to_rfc3339(),
+                    "duration": format_duration_report(entry.duration()),
+                    "minutes": entry.duration().num_minutes(),
+                    "note": entry.note,
+                })
+            })
+            .collect();
+
+        let period = details.period.map(|period| {
+            json!({
+                "first_day": period.first_day,
+                "last_day": period.last_day,
+            })
+        });
+
+        to_json(&json!({
+            "generated_at": details.generated_at,
+            "period": period,
+            "time_frames": time_frames,
+            "total": format_duration_report(details.total()),
+            "total_minutes": details.total().num_minutes(),
+        }))
+    }
+}
+
+impl Renderer for Markdown {
+    fn render_summary(&self, report: &Summary) -> String {
+        let mut markdown = format!(
+            "# Time report\n\n{}, generated {}.\n\n| {} | Duration |\n| --- | ---: |\n",
+            capitalize(&report.description()),
+            report.generated_at.format(TIMESTAMP_FORMAT),
+            report.group_by.label()
+        );
+
+        for row in &report.rows {
+            markdown.push_str(&format!(
+                "| {} | {} |\n",
+                escape_markdown(&row.name),
+                format_duration_report(row.duration)
+            ));
+        }
+        markdown.push_str(&format!(
+            "| **Total** | **{}** |\n",
+            format_duration_report(report.total)
+        ));
+
+        markdown
+    }
+
+    fn render_timesheet(&self, timesheet: &Timesheet) -> String {
+        let days: Vec<_> = timesheet
+            .days
+            .iter()
+            .map(|day| day.format(TIMESHEET_DAY_FORMAT).to_string())
+            .collect();
+
+        let mut markdown = format!(
+            "# Timesheet\n\n{}, generated {}.\n\n| Project | {} | Total |\n| --- |{} ---: |\n",
+            capitalize(&timesheet.description()),
+            timesheet.generated_at.format(TIMESTAMP_FORMAT),
+            days.join(" | "),
+            " ---: |".repeat(days.len())
+        );
+
+        for row in &timesheet.rows {
+            markdown.push_str(&format!(
+                "| {} | {} | {} |\n",
+                escape_markdown(&row.project),
+                join_cells(&row.days, " | "),
+                format_duration_report(row.total())
+            ));
+        }
+        markdown.push_str(&format!(
+            "| **Total** | {} | **{}** |\n",
+            join_cells(&timesheet.day_totals(), " | "),
+            format_duration_report(timesheet.total())
+        ));
+
+        markdown
+    }
+
+    fn render_details(&self, details: &Details) -> String {
+        let mut markdown = format!(
+            "# Time frames\n\n{}, generated {}.\n\n\
+             | Project | Task | Start | End | Duration | Note |\n\
+             | --- | --- | --- | --- | ---: | --- |\n",
+            capitalize(&details.description()),
+            details.generated_at.format(TIMESTAMP_FORMAT)
+        );
+
+        for entry in &details.entries {
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                escape_markdown(&entry.project),
+                escape_markdown(&entry.task),
+                entry.start_time.format(TIMESTAMP_FORMAT),
+                entry.end_time.format(TIMESTAMP_FORMAT),
+                format_duration_report(entry.duration()),
+                escape_markdown(entry.note.as_deref().unwrap_or_default())
+            ));
+        }
+        markdown.push_str(&format!(
+            "| **Total** | | | | **{}** | |\n",
+            format_duration_report(details.total())
+        ));
+
+        markdown
+    }
+}
+
+impl Renderer for Html {
+    fn render_summary(&self, report: &Summary) -> String {
+        let rows: String = report
+            .rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "      <tr><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&row.name),
+                    format_duration_report(row.duration)
+                )
+            })
+            .collect();
+
+        html_page(
+            "Time report",
+            &report.description(),
+            report.generated_at,
+            &format!(
+                r#"    <thead>
+      <tr><th>{}</th><th>Duration</th></tr>
+    </thead>
+    <tbody>
+{}    </tbody>
+    <tfoot>
+      <tr><td>Total</td><td>{}</td></tr>
+    </tfoot>
+"#,
+                report.group_by.label(),
+                rows,
+                format_duration_report(report.total)
+            ),
+        )
+    }
+
+    fn render_timesheet(&self, timesheet: &Timesheet) -> String {
+        let days: String = timesheet
+            .days
+            .iter()
+            .map(|day| format!("<th>{}</th>", day.format(TIMESHEET_DAY_FORMAT)))
+            .collect();
+        let cells = |durations: &[Duration]| -> String {
+            durations
+                .iter()
+                .map(|duration| format!("<td>{}</td>", format_cell(*duration)))
+                .collect()
+        };
+
+        let rows: String = timesheet
+            .rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "      <tr><td>{}</td>{}<td>{}</td></tr>\n",
+                    escape_html(&row.project),
+                    cells(&row.days),
+                    format_duration_report(row.total())
+                )
+            })
+            .collect();
+
+        html_page(
+            "Timesheet",
+            &timesheet.description(),
+            timesheet.generated_at,
+            &format!(
+                r#"    <thead>
+      <tr><th>Project</th>{}<th>Total</th></tr>
+    </thead>
+    <tbody>
+{}    </tbody>
+    <tfoot>
+      <tr><td>Total</td>{}<td>{}</td></tr>
+    </tfoot>
+"#,
+                days,
+                rows,
+                cells(&timesheet.day_totals()),
+                format_duration_report(timesheet.total())
+            ),
+        )
+    }
+
+    fn render_details(&self, details: &Details) -> String {
+        let rows: String = details
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "      <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&entry.project),
+                    escape_html(&entry.task),
+                    entry.start_time.format(TIMESTAMP_FORMAT),
+                    entry.end_time.format(TIMESTAMP_FORMAT),
+                    format_duration_report(entry.duration()),
+                    escape_html(entry.note.as_deref().unwrap_or_default())
+                )
+            })
+            .collect();
+
+        html_page(
+            "Time frames",
+            &details.description(),
+            details.generated_at,
+            &format!(
+                r#"    <thead>
+      <tr><th>Project</th><th>Task</th><th>Start</th><th>End</th><th>Duration</th><th>Note</th></tr>
+    </thead>
+    <tbody>
+{}    </tbody>
+    <tfoot>
+      <tr><td>Total</td><td></td><td></td><td></td><td>{}</td><td></td></tr>
+    </tfoot>
+"#,
+                rows,
+                format_duration_report(details.total())
+            ),
+        )
+    }
+}
+
+/// Wraps the content of a table in a standalone HTML page.
+fn html_page(title: &str, description: &str, generated_at: DateTime<Tz>, table: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>{title}</title>
+  <style>
+    body {{ font-family: sans-serif; margin: 2em; }}
+    table {{ border-collapse: collapse; }}
+    th, td {{ border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: right; }}
+    td:first-child, th:first-child {{ text-align: left; }}
+    tfoot {{ font-weight: bold; }}
+  </style>
+</head>
+<body>
+  <h1>{title}</h1>
+  <p>{}, generated {}.</p>
+  <table>
+{table}  </table>
+</body>
+</html>
+"#,
+        escape_html(&capitalize(description)),
+        generated_at.format(TIMESTAMP_FORMAT),
+    )
+}
+
+fn to_json(value: &serde_json::Value) -> String {
+    let mut json = serde_json::to_string_pretty(value).expect("JSON values serialize");
+    json.push('\n');
+    json
+}
+
+/// Formats a timesheet cell, leaving days without any time blank.
+pub fn format_cell(duration: Duration) -> String {
+    if duration.is_zero() {
+        String::new()
+    } else {
+        format_duration_report(duration)
+    }
+}
+
+fn join_cells(durations: &[Duration], separator: &str) -> String {
+    let cells: Vec<_> = durations
+        .iter()
+        .map(|duration| format_cell(*duration))
+        .collect();
+    cells.join(separator)
+}
+
+fn push_csv_row(csv: &mut String, fields: &[impl AsRef<str>]) {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|field| escape_csv(field.as_ref()))
+        .collect();
+    csv.push_str(&fields.join(","));
+    csv.push('\n');
+}
+
+/// Quotes a field if it contains a separator, quote or line break, doubling any quotes.
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('<', "&lt;")
+        .replace(['\n', '\r'], " ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{
+        report::{Grouping, SummaryRow},
+        rounding::RoundingPolicy,
+    };
+
+    fn summary(name: &str) -> Summary {
+        Summary {
+            generated_at: Tz::UTC.with_ymd_and_hms(2024, 5, 2, 12, 0, 0).unwrap(),
+            period: None,
+            rounding: RoundingPolicy::default(),
+            group_by: Grouping::Project,
+            rows: vec![SummaryRow {
+                name: name.to_owned(),
+                duration: Duration::minutes(90),
+            }],
+            total: Duration::minutes(90),
+        }
+    }
+
+    #[test]
+    fn leaves_plain_csv_fields_unquoted() {
+        assert_eq!(escape_csv("Website"), "Website");
+        assert_eq!(escape_csv(""), "");
+    }
+
+    #[test]
+    fn quotes_csv_fields_with_separators_quotes_and_line_breaks() {
+        assert_eq!(escape_csv("Acme, Inc."), "\"Acme, Inc.\"");
+        assert_eq!(escape_csv("The \"new\" site"), "\"The \"\"new\"\" site\"");
+        assert_eq!(escape_csv("first\nsecond"), "\"first\nsecond\"");
+        assert_eq!(escape_csv("first\r\nsecond"), "\"first\r\nsecond\"");
+    }
+
+    #[test]
+    fn writes_one_csv_row_per_line() {
+        let mut csv = String::new();
+        push_csv_row(&mut csv, &["a,b", "c"]);
+
+        assert_eq!(csv, "\"a,b\",c\n");
+    }
+
+    #[test]
+    fn escapes_markdown_table_cells() {
+        assert_eq!(escape_markdown("A | B"), "A \\| B");
+        assert_eq!(escape_markdown("<b>"), "&lt;b>");
+        assert_eq!(escape_markdown("a\\b"), "a\\\\b");
+        assert_eq!(escape_markdown("first\nsecond"), "first second");
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape_html("<script>Fish & \"Chips\"</script>"),
+            "&lt;script&gt;Fish &amp; &quot;Chips&quot;&lt;/script&gt;"
+        );
+        // Ampersands are escaped first, so entities are not escaped twice
+        assert_eq!(escape_html("&lt;"), "&amp;lt;");
+    }
+
+    #[test]
+    fn escapes_project_names_in_markdown_reports() {
+        let markdown = Markdown.render_summary(&summary("R&D | <internal>"));
+
+        assert!(markdown.contains("| R&D \\| &lt;internal> | 01:30 |\n"));
+    }
+
+    #[test]
+    fn escapes_project_names_in_html_reports() {
+        let html = Html.render_summary(&summary("R&D | <internal>"));
+
+        assert!(html.contains("R&amp;D | &lt;internal&gt;"));
+        assert!(!html.contains("<internal>"));
+    }
+}
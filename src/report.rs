@@ -0,0 +1,301 @@
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Duration, Utc,
+};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    config::{Config, LatestReport, ReportConfig},
+    details::Details,
+    period::Period,
+    render::{Csv, Html, Json, Markdown, Renderer},
+    rounding::RoundingPolicy,
+    timesheet::Timesheet,
+    Error, Task,
+};
+
+/// Writes the report to standard output instead of a file when given as the output path.
+pub const STDOUT_PATH: &str = "-";
+
+/// File name, without extension, under which the newest report is kept next to the archive.
+const LATEST_FILE_STEM: &str = "latest_report";
+
+/// Row of projects without a client when grouping by client.
+const NO_CLIENT: &str = "(no client)";
+/// Row of projects without tags when grouping by tag.
+const NO_TAG: &str = "(untagged)";
+
+/// A report ready to be rendered in any format.
+pub enum Report {
+    Summary(Summary),
+    Timesheet(Timesheet),
+    Details(Details),
+}
+
+/// Time spent per project, client or tag, rounded.
+pub struct Summary {
+    /// In the time zone days were split in
+    pub generated_at: DateTime<Tz>,
+    /// `None` if the report covers all recorded time
+    pub period: Option<Period>,
+    pub rounding: RoundingPolicy,
+    pub group_by: Grouping,
+    pub rows: Vec<SummaryRow>,
+    /// Time of all projects. With tags, this can be less than the sum of the rows, as projects
+    /// with several tags are counted in each of them.
+    pub total: Duration,
+}
+
+pub struct SummaryRow {
+    /// Project, client or tag, depending on `Summary::group_by`
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// What the rows of a summary report add up time for.
+#[derive(Deserialize, ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Grouping {
+    #[default]
+    Project,
+    Client,
+    Tag,
+}
+
+#[derive(Deserialize, ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Csv,
+    Json,
+    Markdown,
+    /// A standalone HTML page
+    Html,
+}
+
+impl Report {
+    pub fn generated_at(&self) -> DateTime<Tz> {
+        match self {
+            Report::Summary(summary) => summary.generated_at,
+            Report::Timesheet(timesheet) => timesheet.generated_at,
+            Report::Details(details) => details.generated_at,
+        }
+    }
+
+    /// `None` if the report covers all recorded time.
+    pub fn period(&self) -> Option<Period> {
+        match self {
+            Report::Summary(summary) => summary.period,
+            Report::Timesheet(timesheet) => Some(timesheet.period),
+            Report::Details(details) => details.period,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        let renderer = format.renderer();
+        match self {
+            Report::Summary(summary) => renderer.render_summary(summary),
+            Report::Timesheet(timesheet) => renderer.render_timesheet(timesheet),
+            Report::Details(details) => renderer.render_details(details),
+        }
+    }
+}
+
+impl Summary {
+    /// Sums up the time per project, and then per client or tag as configured. With a period,
+    /// time frames are clipped to it and projects without any time in it are left out.
+    pub fn build(tasks: &[Task], config: &Config, period: Option<Period>) -> Self {
+        let time_zone = config.time_zone();
+        let projects: Vec<_> = tasks
+            .iter()
+            .filter_map(|task| {
+                let mut times = task.report_times(config.merge_gap_on_report());
+                if let Some(period) = &period {
+                    times = times
+                        .iter()
+                        .filter_map(|time_frame| period.clip(time_frame, time_zone))
+                        .collect();
+                    if times.is_empty() {
+                        return None;
+                    }
+                }
+
+                Some((task, config.rounding.total(&times, time_zone)))
+            })
+            .collect();
+
+        let group_by = config.report.group_by;
+        let mut rows: Vec<SummaryRow> = vec![];
+        for (task, duration) in &projects {
+            for name in group_by.groups(task) {
+                match rows.iter_mut().find(|row| row.name == name) {
+                    Some(row) => row.duration = row.duration + *duration,
+                    None => rows.push(SummaryRow {
+                        name,
+                        duration: *duration,
+                    }),
+                }
+            }
+        }
+
+        Summary {
+            generated_at: Utc::now().with_timezone(&time_zone),
+            period,
+            rounding: config.rounding,
+            group_by,
+            rows,
+            // Projects are rounded before they are grouped, so that totals match across groupings
+            total: projects
+                .iter()
+                .fold(Duration::zero(), |acc, (_, duration)| acc + *duration),
+        }
+    }
+
+    /// Describes what the durations cover, e.g. `2024-05-01 to 2024-05-31 (rounded up to 15 min
+    /// per project)`.
+    pub fn description(&self) -> String {
+        match &self.period {
+            Some(period) => format!("{} ({})", period, self.rounding),
+            None => format!("all time ({})", self.rounding),
+        }
+    }
+}
+
+impl Grouping {
+    /// Column header of the rows.
+    pub fn label(self) -> &'static str {
+        match self {
+            Grouping::Project => "Project",
+            Grouping::Client => "Client",
+            Grouping::Tag => "Tag",
+        }
+    }
+
+    /// Names of the rows the time of `task` is added to.
+    fn groups(self, task: &Task) -> Vec<String> {
+        match self {
+            Grouping::Project => vec![task.project.clone()],
+            Grouping::Client => vec![task.client.clone().unwrap_or_else(|| NO_CLIENT.to_owned())],
+            Grouping::Tag if task.tags.is_empty() => vec![NO_TAG.to_owned()],
+            Grouping::Tag => task.tags.clone(),
+        }
+    }
+}
+
+impl ReportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+
+    pub fn renderer(self) -> Box<dyn Renderer> {
+        match self {
+            ReportFormat::Csv => Box::new(Csv),
+            ReportFormat::Json => Box::new(Json),
+            ReportFormat::Markdown => Box::new(Markdown),
+            ReportFormat::Html => Box::new(Html),
+        }
+    }
+}
+
+/// Renders the report and writes it to `path`, creating its directory if needed, or to
+/// standard output if `path` is `STDOUT_PATH`.
+pub fn write_report(report: &Report, format: ReportFormat, path: &Path) -> Result<(), Error> {
+    let content = report.render(format);
+
+    if path.as_os_str() == STDOUT_PATH {
+        return std::io::stdout()
+            .write_all(content.as_bytes())
+            .map_err(Error::WriteReportError);
+    }
+
+    if let Some(report_dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(report_dir).map_err(Error::WriteReportError)?;
+    }
+    fs::write(path, content).map_err(Error::WriteReportError)
+}
+
+/// Writes the report to a new file in the report directory, named after the configured template,
+/// and points `latest_report.<extension>` at it.
+pub fn archive_report(
+    report: &Report,
+    format: ReportFormat,
+    config: &ReportConfig,
+) -> Result<PathBuf, Error> {
+    let file_name = format!("{}.{}", archive_name(report, config)?, format.extension());
+    let report_path = config.dir.join(&file_name);
+    write_report(report, format, &report_path)?;
+
+    let latest_path = config
+        .dir
+        .join(format!("{}.{}", LATEST_FILE_STEM, format.extension()));
+    match fs::remove_file(&latest_path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(Error::WriteReportError(e)),
+        _ => {}
+    }
+
+    match config.latest {
+        LatestReport::Symlink => {
+            // Relative, so the directory can be moved
+            if symlink(Path::new(&file_name), &latest_path).is_err() {
+                fs::copy(&report_path, &latest_path).map_err(Error::WriteReportError)?;
+            }
+        }
+        LatestReport::Copy => {
+            fs::copy(&report_path, &latest_path).map_err(Error::WriteReportError)?;
+        }
+        LatestReport::None => {}
+    }
+
+    Ok(report_path)
+}
+
+fn archive_name(report: &Report, config: &ReportConfig) -> Result<String, Error> {
+    let period = match report.period() {
+        Some(period) if period.first_day == period.last_day => period.first_day.to_string(),
+        Some(period) => format!("{}_{}", period.first_day, period.last_day),
+        None => "all".to_owned(),
+    };
+    let template = config.name.replace("{period}", &period);
+
+    let items: Vec<_> = StrftimeItems::new(&template).collect();
+    if items.contains(&Item::Error) || template.contains(['/', '\\']) {
+        return Err(Error::InvalidReportName(config.name.clone()));
+    }
+
+    Ok(report
+        .generated_at()
+        .format_with_items(items.into_iter())
+        .to_string())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(ErrorKind::Unsupported.into())
+}
+
+/// Formats an already rounded duration as `HH:MM`.
+pub fn format_duration_report(duration: Duration) -> String {
+    let total_minutes = duration.num_minutes();
+    let minutes = total_minutes % 60;
+    let hours = total_minutes / 60;
+
+    format!("{:0>2}:{:0>2}", hours, minutes)
+}
@@ -0,0 +1,248 @@
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{time_zone::local_day, TimeFrame};
+
+/// Increments, in minutes, that durations can be rounded to.
+const INCREMENTS: [u32; 4] = [1, 6, 15, 30];
+
+/// How reported durations are rounded.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(default)]
+pub struct RoundingPolicy {
+    pub mode: RoundingMode,
+    pub increment: Increment,
+    pub scope: RoundingScope,
+}
+
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingMode {
+    Nearest,
+    Up,
+    Down,
+}
+
+/// Granularity at which rounding is applied before summing up a project's total.
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingScope {
+    /// Round every time frame
+    Frame,
+    /// Round the time spent on each day
+    Day,
+    /// Round the project's total only
+    Project,
+}
+
+/// Rounding increment in minutes, one of `INCREMENTS`.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(try_from = "u32")]
+pub struct Increment(u32);
+
+impl Default for RoundingPolicy {
+    fn default() -> Self {
+        RoundingPolicy {
+            mode: RoundingMode::Up,
+            increment: Increment(15),
+            scope: RoundingScope::Project,
+        }
+    }
+}
+
+impl RoundingPolicy {
+    pub fn round(&self, duration: Duration) -> Duration {
+        let increment = i64::from(self.increment.0) * 60;
+        let seconds = duration.num_seconds();
+
+        let increments = match self.mode {
+            RoundingMode::Nearest => (seconds + increment / 2).div_euclid(increment),
+            RoundingMode::Up => (seconds + increment - 1).div_euclid(increment),
+            RoundingMode::Down => seconds.div_euclid(increment),
+        };
+
+        Duration::seconds(increments * increment)
+    }
+
+    /// Sums up the time frames of a project, rounding at the policy's scope. Time frames are
+    /// assigned to the day they start on in `time_zone`.
+    pub fn total(&self, times: &[TimeFrame], time_zone: Tz) -> Duration {
+        match self.scope {
+            RoundingScope::Frame => times.iter().fold(Duration::zero(), |acc, time_frame| {
+                acc + self.round(time_frame.duration())
+            }),
+            RoundingScope::Day => {
+                let mut days: Vec<(NaiveDate, Duration)> = vec![];
+                for time_frame in times {
+                    let day = local_day(time_frame.start_time, time_zone);
+                    match days.iter_mut().find(|(other, _)| *other == day) {
+                        Some((_, total)) => *total = *total + time_frame.duration(),
+                        None => days.push((day, time_frame.duration())),
+                    }
+                }
+
+                days.iter()
+                    .fold(Duration::zero(), |acc, (_, total)| acc + self.round(*total))
+            }
+            RoundingScope::Project => {
+                self.round(times.iter().fold(Duration::zero(), |acc, time_frame| {
+                    acc + time_frame.duration()
+                }))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RoundingPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            RoundingMode::Nearest => "to the nearest",
+            RoundingMode::Up => "up to",
+            RoundingMode::Down => "down to",
+        };
+        let scope = match self.scope {
+            RoundingScope::Frame => "time frame",
+            RoundingScope::Day => "day",
+            RoundingScope::Project => "project",
+        };
+
+        write!(f, "rounded {} {} min per {}", mode, self.increment.0, scope)
+    }
+}
+
+impl TryFrom<u32> for Increment {
+    type Error = String;
+
+    fn try_from(minutes: u32) -> Result<Self, Self::Error> {
+        if INCREMENTS.contains(&minutes) {
+            Ok(Increment(minutes))
+        } else {
+            Err(format!(
+                "invalid rounding increment {}, expected one of {:?}",
+                minutes, INCREMENTS
+            ))
+        }
+    }
+}
+
+impl std::str::FromStr for Increment {
+    type Err = String;
+
+    fn from_str(minutes: &str) -> Result<Self, Self::Err> {
+        minutes
+            .parse::<u32>()
+            .map_err(|e| e.to_string())?
+            .try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::test_support::{time_frame, utc};
+
+    fn policy(mode: RoundingMode, increment: u32, scope: RoundingScope) -> RoundingPolicy {
+        RoundingPolicy {
+            mode,
+            increment: Increment(increment),
+            scope,
+        }
+    }
+
+    /// A time frame of `minutes` on a day in May 2024.
+    fn worked(day: u32, hour: u32, minutes: i64) -> TimeFrame {
+        let start_time = utc(2024, 5, day, hour, 0);
+
+        time_frame(
+            Uuid::nil(),
+            start_time,
+            start_time + Duration::minutes(minutes),
+        )
+    }
+
+    #[test]
+    fn rounds_by_mode() {
+        let duration = Duration::minutes(67);
+
+        let rounded = |mode| policy(mode, 15, RoundingScope::Project).round(duration);
+
+        assert_eq!(rounded(RoundingMode::Up), Duration::minutes(75));
+        assert_eq!(rounded(RoundingMode::Down), Duration::minutes(60));
+        assert_eq!(rounded(RoundingMode::Nearest), Duration::minutes(60));
+    }
+
+    #[test]
+    fn rounds_half_increments_up_with_nearest() {
+        let nearest = policy(RoundingMode::Nearest, 6, RoundingScope::Project);
+
+        assert_eq!(nearest.round(Duration::minutes(3)), Duration::minutes(6));
+        assert_eq!(
+            nearest.round(Duration::minutes(3) - Duration::seconds(1)),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn keeps_exact_multiples() {
+        for increment in INCREMENTS {
+            for mode in [RoundingMode::Nearest, RoundingMode::Up, RoundingMode::Down] {
+                let duration = Duration::minutes(i64::from(increment) * 7);
+
+                assert_eq!(
+                    policy(mode, increment, RoundingScope::Project).round(duration),
+                    duration
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rounds_at_scope() {
+        // Two days with two 10 minute sessions each
+        let times = [
+            worked(1, 9, 10),
+            worked(1, 14, 10),
+            worked(2, 9, 10),
+            worked(2, 14, 10),
+        ];
+
+        let total = |scope| policy(RoundingMode::Up, 15, scope).total(&times, Tz::UTC);
+
+        assert_eq!(total(RoundingScope::Frame), Duration::minutes(60));
+        assert_eq!(total(RoundingScope::Day), Duration::minutes(60));
+        assert_eq!(total(RoundingScope::Project), Duration::minutes(45));
+    }
+
+    #[test]
+    fn rounds_days_in_time_zone() {
+        // 23:00 and 01:00 in Berlin, on different days there but the same day in UTC
+        let times = [worked(1, 21, 10), worked(1, 23, 10)];
+
+        let total =
+            |time_zone| policy(RoundingMode::Up, 30, RoundingScope::Day).total(&times, time_zone);
+
+        assert_eq!(total(Tz::UTC), Duration::minutes(30));
+        assert_eq!(total(Tz::Europe__Berlin), Duration::minutes(60));
+    }
+
+    #[test]
+    fn rejects_unsupported_increments() {
+        assert!(Increment::try_from(15).is_ok());
+        assert!(Increment::try_from(10).is_err());
+        assert!("0".parse::<Increment>().is_err());
+    }
+
+    #[test]
+    fn describes_policy() {
+        assert_eq!(
+            RoundingPolicy::default().to_string(),
+            "rounded up to 15 min per project"
+        );
+    }
+}
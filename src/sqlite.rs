@@ -0,0 +1,425 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use rusqlite::{params, Connection, ErrorCode, Transaction, TransactionBehavior};
+use serde_json::{json, Value};
+
+use crate::{
+    db::sibling_path,
+    migrations::{self, SCHEMA_VERSION},
+    storage::{Data, Storage, Updater},
+    Error,
+};
+
+/// How long an update waits for another process to finish its write transaction.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CREATE_TABLES: &str = "
+    CREATE TABLE tasks (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE time_frames (
+        id TEXT PRIMARY KEY,
+        task_id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX time_frames_task_id ON time_frames (task_id);
+";
+
+/// Added in schema version 2. Rows hold the whole deleted task including its time frames, as
+/// they are not changed until the task is restored.
+const CREATE_TRASH_TABLE: &str = "
+    CREATE TABLE trash (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+";
+
+/// Stores tasks and time frames as rows of an SQLite database, so that an update only writes
+/// the rows that changed. Rows hold the serialized task (without its time frames) or time frame,
+/// which lets the model gain fields without table changes. The schema version is kept in
+/// `PRAGMA user_version` and upgraded through the same migrations as the JSON file.
+pub struct SqliteStorage {
+    db_path: PathBuf,
+    connection: Connection,
+}
+
+/// Rows as they were read, keyed by ID, to find the ones an update changed.
+#[derive(Default)]
+struct Snapshot {
+    tasks: HashMap<String, (usize, String)>,
+    time_frames: HashMap<String, (String, usize, String)>,
+    trash: HashMap<String, (usize, String)>,
+}
+
+impl SqliteStorage {
+    pub fn open(db_path: PathBuf) -> Result<Self, Error> {
+        if let Some(db_dir) = db_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(db_dir)?;
+        }
+
+        let connection = Connection::open(&db_path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+
+        let storage = SqliteStorage {
+            db_path,
+            connection,
+        };
+
+        let transaction = storage.begin(TransactionBehavior::Immediate)?;
+        let version = schema_version(&transaction)?;
+        if (1..SCHEMA_VERSION).contains(&version) {
+            // Keep the file as it was before the upgrade, like the JSON storage does. Copying is
+            // safe while the transaction holds the write lock, as nothing was written yet.
+            fs::copy(
+                &storage.db_path,
+                sibling_path(&storage.db_path, &format!("v{}.bak", version)),
+            )?;
+        }
+        match version {
+            0 => {
+                transaction.execute_batch(CREATE_TABLES)?;
+                transaction.execute_batch(CREATE_TRASH_TABLE)?;
+                set_schema_version(&transaction)?;
+            }
+            // The data itself is upgraded on the first read
+            1 => transaction.execute_batch(CREATE_TRASH_TABLE)?,
+            _ => {}
+        }
+        transaction.commit()?;
+
+        Ok(storage)
+    }
+
+    fn begin(&self, behavior: TransactionBehavior) -> Result<Transaction<'_>, Error> {
+        Transaction::new_unchecked(&self.connection, behavior).map_err(|e| {
+            match e.sqlite_error_code() {
+                Some(ErrorCode::DatabaseBusy) => Error::DBLocked(self.db_path.clone()),
+                _ => Error::SqliteError(e),
+            }
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn read_data(&self) -> Result<Data, Error> {
+        let transaction = self.begin(TransactionBehavior::Deferred)?;
+        let (data, migrated_from, _) = load(&transaction)?;
+        drop(transaction);
+
+        if migrated_from.is_some() {
+            // Persist the upgrade right away, like the JSON storage does
+            let storage: &dyn Storage = self;
+            return storage.update_data(|data| data.clone());
+        }
+
+        Ok(data)
+    }
+
+    fn apply_update(&self, updater: Updater<'_>) -> Result<(), Error> {
+        // An immediate transaction takes the write lock before reading
+        let transaction = self.begin(TransactionBehavior::Immediate)?;
+        let (mut data, _, snapshot) = load(&transaction)?;
+
+        updater(&mut data);
+
+        save(&transaction, &data, snapshot)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.db_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn path(&self) -> &Path {
+        &self.db_path
+    }
+}
+
+fn schema_version(connection: &Connection) -> Result<u32, Error> {
+    Ok(connection.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+fn set_schema_version(connection: &Connection) -> Result<(), Error> {
+    connection.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))?;
+
+    Ok(())
+}
+
+/// Reads all rows into the envelope layout of the JSON file and upgrades it to the current
+/// schema version.
+fn load(connection: &Connection) -> Result<(Data, Option<u32>, Snapshot), Error> {
+    let mut snapshot = Snapshot::default();
+    let mut tasks = vec![];
+    let mut task_positions = HashMap::new();
+
+    let mut statement = connection.prepare("SELECT id, data FROM tasks ORDER BY position")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let data: String = row.get(1)?;
+
+        let mut task: Value = serde_json::from_str(&data)?;
+        if let Some(task) = task.as_object_mut() {
+            task.insert("times".to_owned(), json!([]));
+        }
+
+        task_positions.insert(id.clone(), tasks.len());
+        snapshot.tasks.insert(id, (tasks.len(), data));
+        tasks.push(task);
+    }
+
+    let mut statement = connection.prepare(
+        "SELECT id, task_id, position, data FROM time_frames ORDER BY task_id, position",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let task_id: String = row.get(1)?;
+        let position: usize = row.get(2)?;
+        let data: String = row.get(3)?;
+
+        if let Some(&task_position) = task_positions.get(&task_id) {
+            if let Some(times) = tasks[task_position]["times"].as_array_mut() {
+                times.push(serde_json::from_str(&data)?);
+            }
+        }
+        snapshot.time_frames.insert(id, (task_id, position, data));
+    }
+
+    let mut trash = vec![];
+    let mut statement = connection.prepare("SELECT id, data FROM trash ORDER BY position")?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let data: String = row.get(1)?;
+
+        trash.push(serde_json::from_str::<Value>(&data)?);
+        snapshot.trash.insert(id, (trash.len() - 1, data));
+    }
+
+    let db = json!({
+        "schema_version": schema_version(connection)?,
+        "tasks": tasks,
+        "trash": trash,
+    });
+    let (upgraded, migrated_from) = migrations::upgrade(db)?;
+    let data = serde_json::from_value(upgraded)?;
+
+    Ok((data, migrated_from, snapshot))
+}
+
+/// Writes the rows that differ from `snapshot` and deletes the ones that no longer exist.
+fn save(connection: &Connection, data: &Data, mut snapshot: Snapshot) -> Result<(), Error> {
+    for (position, task) in data.tasks.iter().enumerate() {
+        let task_id = task.id.to_string();
+
+        let mut task_data = serde_json::to_value(task)?;
+        if let Some(task_data) = task_data.as_object_mut() {
+            task_data.remove("times");
+        }
+        let row = (position, task_data.to_string());
+
+        if snapshot.tasks.remove(&task_id).as_ref() != Some(&row) {
+            connection.execute(
+                "INSERT OR REPLACE INTO tasks (id, position, data) VALUES (?1, ?2, ?3)",
+                params![task_id, row.0, row.1],
+            )?;
+        }
+
+        for (position, time_frame) in task.times.iter().enumerate() {
+            let time_frame_id = time_frame.id.to_string();
+            let row = (
+                task_id.clone(),
+                position,
+                serde_json::to_string(time_frame)?,
+            );
+
+            if snapshot.time_frames.remove(&time_frame_id).as_ref() != Some(&row) {
+                connection.execute(
+                    "INSERT OR REPLACE INTO time_frames (id, task_id, position, data)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![time_frame_id, row.0, row.1, row.2],
+                )?;
+            }
+        }
+    }
+
+    for (position, trashed) in data.trash.iter().enumerate() {
+        let id = trashed.task.id.to_string();
+        let row = (position, serde_json::to_string(trashed)?);
+
+        if snapshot.trash.remove(&id).as_ref() != Some(&row) {
+            connection.execute(
+                "INSERT OR REPLACE INTO trash (id, position, data) VALUES (?1, ?2, ?3)",
+                params![id, row.0, row.1],
+            )?;
+        }
+    }
+
+    // Whatever is left in the snapshot was removed by the update
+    for id in snapshot.trash.keys() {
+        connection.execute("DELETE FROM trash WHERE id = ?1", [id])?;
+    }
+    for id in snapshot.tasks.keys() {
+        connection.execute("DELETE FROM tasks WHERE id = ?1", [id])?;
+    }
+    for id in snapshot.time_frames.keys() {
+        connection.execute("DELETE FROM time_frames WHERE id = ?1", [id])?;
+    }
+
+    set_schema_version(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{
+        test_support::{time_frame, utc, TempDir},
+        trash::TrashedTask,
+        Task,
+    };
+
+    fn task(project: &str, hours: &[u32]) -> Task {
+        let mut task = Task::new(project.to_owned());
+        let subtask_id = task.subtasks[0].id;
+        for &hour in hours {
+            let start_time = utc(2024, 5, 2, hour, 0);
+            task.times.push(time_frame(
+                subtask_id,
+                start_time,
+                start_time + Duration::minutes(30),
+            ));
+        }
+
+        task
+    }
+
+    fn to_json(data: &Data) -> Value {
+        serde_json::to_value(data).unwrap()
+    }
+
+    fn row_count(storage: &SqliteStorage, table: &str) -> usize {
+        storage
+            .connection
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    /// Applies `updater` and checks that the data reads back as it was left.
+    fn update(storage: &SqliteStorage, updater: impl FnOnce(&mut Data)) {
+        let storage: &dyn Storage = storage;
+        let expected = storage
+            .update_data(|data| {
+                updater(data);
+                to_json(data)
+            })
+            .unwrap();
+
+        assert_eq!(to_json(&storage.read_data().unwrap()), expected);
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let dir = TempDir::new();
+        let storage = SqliteStorage::open(dir.join("db.sqlite3")).unwrap();
+
+        update(&storage, |data| {
+            data.tasks = vec![task("Website", &[9, 10]), task("Mobile app", &[11])];
+            data.trash = vec![TrashedTask::new(task("Old", &[8]))];
+        });
+
+        assert_eq!(row_count(&storage, "tasks"), 2);
+        assert_eq!(row_count(&storage, "time_frames"), 3);
+        assert_eq!(row_count(&storage, "trash"), 1);
+    }
+
+    #[test]
+    fn keeps_the_order_of_reordered_tasks_and_time_frames() {
+        let dir = TempDir::new();
+        let storage = SqliteStorage::open(dir.join("db.sqlite3")).unwrap();
+        update(&storage, |data| {
+            data.tasks = vec![task("Website", &[9, 10]), task("Mobile app", &[11])];
+        });
+
+        update(&storage, |data| {
+            data.tasks.reverse();
+            data.tasks[1].times.reverse();
+        });
+    }
+
+    #[test]
+    fn moves_time_frames_between_tasks() {
+        let dir = TempDir::new();
+        let storage = SqliteStorage::open(dir.join("db.sqlite3")).unwrap();
+        update(&storage, |data| {
+            data.tasks = vec![task("Website", &[9, 10]), task("Mobile app", &[11])];
+        });
+
+        update(&storage, |data| {
+            let mut moved = data.tasks[0].times.remove(0);
+            moved.subtask_id = data.tasks[1].subtasks[0].id;
+            data.tasks[1].times.push(moved);
+        });
+
+        assert_eq!(row_count(&storage, "time_frames"), 3);
+    }
+
+    #[test]
+    fn deletes_removed_tasks_and_time_frames() {
+        let dir = TempDir::new();
+        let storage = SqliteStorage::open(dir.join("db.sqlite3")).unwrap();
+        update(&storage, |data| {
+            data.tasks = vec![task("Website", &[9, 10]), task("Mobile app", &[11])];
+        });
+
+        update(&storage, |data| {
+            data.tasks.remove(1);
+            data.tasks[0].times.remove(0);
+        });
+
+        assert_eq!(row_count(&storage, "tasks"), 1);
+        assert_eq!(row_count(&storage, "time_frames"), 1);
+    }
+
+    #[test]
+    fn moves_tasks_to_and_from_the_trash() {
+        let dir = TempDir::new();
+        let storage = SqliteStorage::open(dir.join("db.sqlite3")).unwrap();
+        update(&storage, |data| {
+            data.tasks = vec![task("Website", &[9, 10]), task("Mobile app", &[11])];
+        });
+
+        update(&storage, |data| {
+            let id = data.tasks[0].id;
+            data.trash_task(id, None);
+        });
+        assert_eq!(row_count(&storage, "tasks"), 1);
+        assert_eq!(row_count(&storage, "time_frames"), 1);
+        assert_eq!(row_count(&storage, "trash"), 1);
+
+        update(&storage, |data| {
+            let trashed = data.trash.remove(0);
+            data.tasks.push(trashed.task);
+        });
+        assert_eq!(row_count(&storage, "tasks"), 2);
+        assert_eq!(row_count(&storage, "time_frames"), 3);
+        assert_eq!(row_count(&storage, "trash"), 0);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{path::Path, time::SystemTime};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{db::JsonStorage, sqlite::SqliteStorage, trash::TrashedTask, Error, Task};
+
+#[derive(Deserialize, ValueEnum, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// A single JSON file, rewritten on every change
+    #[default]
+    Json,
+    /// An embedded SQLite database, updated incrementally
+    Sqlite,
+}
+
+impl StorageKind {
+    pub fn default_file_name(self) -> &'static str {
+        match self {
+            StorageKind::Json => "db.json",
+            StorageKind::Sqlite => "db.sqlite3",
+        }
+    }
+}
+
+/// Everything kept in a storage.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Data {
+    pub tasks: Vec<Task>,
+    /// Deleted projects, oldest first
+    pub trash: Vec<TrashedTask>,
+}
+
+/// Mutation of the data passed to `Storage::apply_update`.
+pub type Updater<'a> = Box<dyn FnOnce(&mut Data) + 'a>;
+
+/// Persistence of the task list and trash. Implementations must make `apply_update` atomic
+/// with respect to other processes using the same storage.
+pub trait Storage {
+    fn read_data(&self) -> Result<Data, Error>;
+
+    /// Runs `updater` on the current data and persists the result.
+    fn apply_update(&self, updater: Updater<'_>) -> Result<(), Error>;
+
+    /// Changes whenever any process writes to the storage.
+    fn last_modified(&self) -> Option<SystemTime>;
+
+    fn path(&self) -> &Path;
+}
+
+impl dyn Storage + '_ {
+    pub fn read_db(&self) -> Result<Vec<Task>, Error> {
+        Ok(self.read_data()?.tasks)
+    }
+
+    pub fn update_data<T>(&self, updater: impl FnOnce(&mut Data) -> T) -> Result<T, Error> {
+        let mut result = None;
+        self.apply_update(Box::new(|data| result = Some(updater(data))))?;
+
+        Ok(result.expect("storage runs the updater"))
+    }
+
+    /// Like `update_data`, for changes to the task list only.
+    pub fn update_db<T>(&self, updater: impl FnOnce(&mut Vec<Task>) -> T) -> Result<T, Error> {
+        self.update_data(|data| updater(&mut data.tasks))
+    }
+}
+
+pub fn open(kind: StorageKind, path: &Path) -> Result<Box<dyn Storage>, Error> {
+    Ok(match kind {
+        StorageKind::Json => Box::new(JsonStorage::new(path.to_owned())),
+        StorageKind::Sqlite => Box::new(SqliteStorage::open(path.to_owned())?),
+    })
+}
+
+/// Copies all data from `source` into the empty storage `target` and verifies that it reads
+/// back identically.
+pub fn migrate(source: &dyn Storage, target: &dyn Storage) -> Result<usize, Error> {
+    let data = source.read_data()?;
+    let expected = serde_json::to_value(&data)?;
+
+    target.update_data(|target_data| {
+        if !target_data.tasks.is_empty() || !target_data.trash.is_empty() {
+            return Err(Error::StorageNotEmpty(target.path().to_owned()));
+        }
+        target_data.clone_from(&data);
+
+        Ok(())
+    })??;
+
+    if serde_json::to_value(target.read_data()?)? != expected {
+        return Err(Error::StorageMismatch(target.path().to_owned()));
+    }
+
+    Ok(data.tasks.len())
+}
+
+/// Loads and persists the data of a storage, remembering the version last seen so changes
+/// made by other processes can be detected.
+pub struct Repository {
+    storage: Box<dyn Storage>,
+    last_modified: Option<SystemTime>,
+}
+
+impl Repository {
+    pub fn new(storage: Box<dyn Storage>) -> Self {
+        Repository {
+            storage,
+            last_modified: None,
+        }
+    }
+
+    /// Reads the data. After a failed read, `has_changed` stays true so that it is retried.
+    pub fn load(&mut self) -> Result<Data, Error> {
+        // Taken before reading so a concurrent write is picked up by the next check
+        let last_modified = self.storage.last_modified();
+        let data = self.storage.read_data()?;
+        self.last_modified = last_modified;
+
+        Ok(data)
+    }
+
+    pub fn has_changed(&self) -> bool {
+        self.storage.last_modified() != self.last_modified
+    }
+
+    /// Runs `updater` on the current DB content and returns the persisted data.
+    pub fn update(&mut self, updater: impl FnOnce(&mut Data)) -> Result<Data, Error> {
+        let data = self.storage.update_data(|data| {
+            updater(data);
+            data.clone()
+        })?;
+        self.last_modified = self.storage.last_modified();
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::test_support::{time_frame, utc, TempDir};
+
+    fn sample_data() -> Data {
+        let mut website = Task::new("Website".to_owned());
+        let subtask_id = website.subtasks[0].id;
+        website.times.push(time_frame(
+            subtask_id,
+            utc(2024, 5, 2, 9, 0),
+            utc(2024, 5, 2, 10, 0),
+        ));
+        website.times[0].note = Some("Design, \"v2\"".to_owned());
+        website.client = Some("Acme".to_owned());
+        website.tags = vec!["web".to_owned()];
+
+        let mut old = Task::new("Old".to_owned());
+        let subtask_id = old.subtasks[0].id;
+        old.times.push(time_frame(
+            subtask_id,
+            utc(2024, 4, 2, 9, 0),
+            utc(2024, 4, 2, 9, 0) + Duration::minutes(45),
+        ));
+
+        Data {
+            tasks: vec![website, Task::new("Mobile app".to_owned())],
+            trash: vec![TrashedTask::new(old)],
+        }
+    }
+
+    fn to_json(data: &Data) -> serde_json::Value {
+        serde_json::to_value(data).unwrap()
+    }
+
+    #[test]
+    fn migrates_between_storages_without_loss() {
+        let dir = TempDir::new();
+        let source = open(StorageKind::Json, &dir.join("db.json")).unwrap();
+        let data = sample_data();
+        source
+            .update_data(|source_data| source_data.clone_from(&data))
+            .unwrap();
+
+        let sqlite = open(StorageKind::Sqlite, &dir.join("db.sqlite3")).unwrap();
+        assert_eq!(migrate(source.as_ref(), sqlite.as_ref()).unwrap(), 2);
+
+        let json = open(StorageKind::Json, &dir.join("copy.json")).unwrap();
+        assert_eq!(migrate(sqlite.as_ref(), json.as_ref()).unwrap(), 2);
+
+        assert_eq!(to_json(&json.read_data().unwrap()), to_json(&data));
+    }
+
+    #[test]
+    fn refuses_to_migrate_into_a_storage_with_data() {
+        let dir = TempDir::new();
+        let source = open(StorageKind::Json, &dir.join("db.json")).unwrap();
+        source
+            .update_data(|data| data.clone_from(&sample_data()))
+            .unwrap();
+
+        let target = open(StorageKind::Sqlite, &dir.join("db.sqlite3")).unwrap();
+        target
+            .update_data(|data| data.tasks.push(Task::new("Existing".to_owned())))
+            .unwrap();
+
+        assert!(matches!(
+            migrate(source.as_ref(), target.as_ref()),
+            Err(Error::StorageNotEmpty(_))
+        ));
+        let target_data = target.read_data().unwrap();
+        assert_eq!(target_data.tasks.len(), 1);
+        assert!(target_data.trash.is_empty());
+    }
+}
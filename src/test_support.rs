@@ -0,0 +1,55 @@
+//! Fixtures shared by the unit tests.
+
+use std::{fs, path::PathBuf};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use uuid::Uuid;
+
+use crate::TimeFrame;
+
+pub fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+pub fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+        .unwrap()
+}
+
+/// A time frame without a note, as recorded by stopping a timer.
+pub fn time_frame(
+    subtask_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> TimeFrame {
+    TimeFrame {
+        id: Uuid::new_v4(),
+        subtask_id,
+        start_time,
+        end_time,
+        note: None,
+        after_idle: false,
+    }
+}
+
+/// A directory of its own for a test, removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("time-tracking-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+
+        TempDir(path)
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::TimeFrame;
+
+/// Environment variable overriding the system time zone, as in `TZ=Europe/Berlin`.
+const TZ_ENV_VAR: &str = "TZ";
+
+/// The time zone of the system, from `TZ` or the OS settings, falling back to UTC if it is not
+/// a known IANA name.
+pub fn system_time_zone() -> Tz {
+    std::env::var(TZ_ENV_VAR)
+        .ok()
+        .and_then(|name| name.trim_start_matches(':').parse().ok())
+        .or_else(|| iana_time_zone::get_timezone().ok()?.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// The day `timestamp` falls on in `time_zone`.
+pub fn local_day(timestamp: DateTime<Utc>, time_zone: Tz) -> NaiveDate {
+    timestamp.with_timezone(&time_zone).date_naive()
+}
+
+pub fn today(time_zone: Tz) -> NaiveDate {
+    local_day(Utc::now(), time_zone)
+}
+
+/// The first instant of `day` in `time_zone`. Where a DST transition skips midnight, the day
+/// starts when the clocks resume.
+pub fn start_of_day(day: NaiveDate, time_zone: Tz) -> DateTime<Utc> {
+    let midnight = day.and_hms_opt(0, 0, 0).expect("midnight exists");
+
+    // Transitions move clocks in multiples of 15 minutes, at most a whole day
+    (0..=24 * 4)
+        .find_map(|quarter| {
+            time_zone
+                .from_local_datetime(&(midnight + Duration::minutes(quarter * 15)))
+                .earliest()
+        })
+        .map_or_else(
+            || Utc.from_utc_datetime(&midnight),
+            |start| start.with_timezone(&Utc),
+        )
+}
+
+/// Splits a time frame into the parts falling on each day in `time_zone`.
+pub fn split_by_day(time_frame: &TimeFrame, time_zone: Tz) -> Vec<(NaiveDate, Duration)> {
+    let mut parts = vec![];
+    let mut start_time = time_frame.start_time;
+
+    while start_time < time_frame.end_time {
+        let day = local_day(start_time, time_zone);
+        let next_midnight = day.succ_opt().map_or(time_frame.end_time, |next_day| {
+            start_of_day(next_day, time_zone)
+        });
+
+        let end_time = time_frame.end_time.min(next_midnight);
+        parts.push((day, end_time - start_time));
+        start_time = end_time;
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::test_support::{day, time_frame, utc};
+
+    fn day_length(date: NaiveDate, time_zone: Tz) -> Duration {
+        start_of_day(date.succ_opt().unwrap(), time_zone) - start_of_day(date, time_zone)
+    }
+
+    #[test]
+    fn days_start_at_local_midnight() {
+        assert_eq!(
+            start_of_day(day(2024, 1, 15), Tz::Europe__Berlin),
+            utc(2024, 1, 14, 23, 0)
+        );
+        assert_eq!(
+            start_of_day(day(2024, 7, 15), Tz::Europe__Berlin),
+            utc(2024, 7, 14, 22, 0)
+        );
+        assert_eq!(
+            start_of_day(day(2024, 7, 15), Tz::America__New_York),
+            utc(2024, 7, 15, 4, 0)
+        );
+    }
+
+    #[test]
+    fn dst_changes_the_length_of_days() {
+        let berlin = Tz::Europe__Berlin;
+
+        assert_eq!(day_length(day(2024, 3, 31), berlin), Duration::hours(23));
+        assert_eq!(day_length(day(2024, 10, 27), berlin), Duration::hours(25));
+        assert_eq!(day_length(day(2024, 10, 28), berlin), Duration::hours(24));
+    }
+
+    #[test]
+    fn days_start_after_a_skipped_midnight() {
+        // Chile moved its clocks from 00:00 to 01:00 on 2024-09-08
+        let santiago = Tz::America__Santiago;
+
+        assert_eq!(
+            start_of_day(day(2024, 9, 8), santiago),
+            utc(2024, 9, 8, 4, 0)
+        );
+        assert_eq!(day_length(day(2024, 9, 8), santiago), Duration::hours(23));
+    }
+
+    #[test]
+    fn repeated_hour_before_midnight_lengthens_the_day() {
+        // Chile moved its clocks from 00:00 on 2024-04-07 back to 23:00 on 2024-04-06
+        let santiago = Tz::America__Santiago;
+
+        assert_eq!(
+            start_of_day(day(2024, 4, 6), santiago),
+            utc(2024, 4, 6, 3, 0)
+        );
+        assert_eq!(day_length(day(2024, 4, 6), santiago), Duration::hours(25));
+    }
+
+    #[test]
+    fn splits_at_local_midnight() {
+        // 22:00 to 02:00 in Berlin
+        let time_frame = time_frame(Uuid::nil(), utc(2024, 1, 15, 21, 0), utc(2024, 1, 16, 1, 0));
+
+        assert_eq!(
+            split_by_day(&time_frame, Tz::Europe__Berlin),
+            vec![
+                (day(2024, 1, 15), Duration::hours(2)),
+                (day(2024, 1, 16), Duration::hours(2))
+            ]
+        );
+        assert_eq!(
+            split_by_day(&time_frame, Tz::UTC),
+            vec![
+                (day(2024, 1, 15), Duration::hours(3)),
+                (day(2024, 1, 16), Duration::hours(1))
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_across_dst_change() {
+        // Saturday 20:00 to Sunday 20:00 in Berlin, with 02:00 to 03:00 skipped
+        let time_frame = time_frame(
+            Uuid::nil(),
+            utc(2024, 3, 30, 19, 0),
+            utc(2024, 3, 31, 18, 0),
+        );
+
+        assert_eq!(
+            split_by_day(&time_frame, Tz::Europe__Berlin),
+            vec![
+                (day(2024, 3, 30), Duration::hours(4)),
+                (day(2024, 3, 31), Duration::hours(19))
+            ]
+        );
+    }
+
+    #[test]
+    fn assigns_timestamps_to_local_days() {
+        let timestamp = utc(2024, 10, 27, 23, 30);
+
+        assert_eq!(local_day(timestamp, Tz::UTC), day(2024, 10, 27));
+        assert_eq!(local_day(timestamp, Tz::Europe__Berlin), day(2024, 10, 28));
+        assert_eq!(
+            local_day(timestamp, Tz::America__Los_Angeles),
+            day(2024, 10, 27)
+        );
+    }
+}
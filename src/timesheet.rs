@@ -0,0 +1,227 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    config::Config,
+    period::Period,
+    rounding::{RoundingPolicy, RoundingScope},
+    time_zone::split_by_day,
+    Task,
+};
+
+/// Time per project and day of a period, as entered into timesheet systems.
+pub struct Timesheet {
+    /// In the time zone days were split in
+    pub generated_at: DateTime<Tz>,
+    pub period: Period,
+    /// Applied to every cell, so that the totals add up
+    pub rounding: RoundingPolicy,
+    pub days: Vec<NaiveDate>,
+    pub rows: Vec<TimesheetRow>,
+}
+
+pub struct TimesheetRow {
+    pub project: String,
+    /// Time per day, in the order of `Timesheet::days`
+    pub days: Vec<Duration>,
+}
+
+impl Timesheet {
+    /// Splits the time frames of every task at midnight in the configured time zone and sums them
+    /// up per day. Projects without any time in the period are left out.
+    pub fn build(tasks: &[Task], config: &Config, period: Period) -> Self {
+        let time_zone = config.time_zone();
+        let days: Vec<_> = period.days().collect();
+        let rounding = RoundingPolicy {
+            scope: RoundingScope::Day,
+            ..config.rounding
+        };
+
+        let rows = tasks
+            .iter()
+            .filter_map(|task| {
+                let mut totals = vec![Duration::zero(); days.len()];
+                for time_frame in task.report_times(config.merge_gap_on_report()) {
+                    for (day, duration) in split_by_day(&time_frame, time_zone) {
+                        if let Some(index) = days.iter().position(|other| *other == day) {
+                            totals[index] = totals[index] + duration;
+                        }
+                    }
+                }
+
+                totals
+                    .iter()
+                    .any(|total| !total.is_zero())
+                    .then(|| TimesheetRow {
+                        project: task.project.clone(),
+                        days: totals
+                            .into_iter()
+                            .map(|total| rounding.round(total))
+                            .collect(),
+                    })
+            })
+            .collect();
+
+        Timesheet {
+            generated_at: Utc::now().with_timezone(&time_zone),
+            period,
+            rounding,
+            days,
+            rows,
+        }
+    }
+
+    /// Time per day across all projects.
+    pub fn day_totals(&self) -> Vec<Duration> {
+        (0..self.days.len())
+            .map(|index| {
+                self.rows
+                    .iter()
+                    .fold(Duration::zero(), |acc, row| acc + row.days[index])
+            })
+            .collect()
+    }
+
+    pub fn total(&self) -> Duration {
+        self.rows
+            .iter()
+            .fold(Duration::zero(), |acc, row| acc + row.total())
+    }
+
+    /// Describes what the timesheet covers, e.g. `2024-05-06 to 2024-05-12 (rounded up to 15 min
+    /// per day)`.
+    pub fn description(&self) -> String {
+        format!("{} ({})", self.period, self.rounding)
+    }
+}
+
+impl TimesheetRow {
+    pub fn total(&self) -> Duration {
+        self.days
+            .iter()
+            .fold(Duration::zero(), |acc, duration| acc + *duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{day, time_frame, utc};
+
+    fn task(project: &str, times: &[(DateTime<Utc>, DateTime<Utc>)]) -> Task {
+        let mut task = Task::new(project.to_owned());
+        let subtask_id = task.subtasks[0].id;
+        for &(start_time, end_time) in times {
+            task.times
+                .push(time_frame(subtask_id, start_time, end_time));
+        }
+
+        task
+    }
+
+    fn minutes(minutes: &[i64]) -> Vec<Duration> {
+        minutes
+            .iter()
+            .map(|minutes| Duration::minutes(*minutes))
+            .collect()
+    }
+
+    /// The week of Monday 2024-05-06 in Berlin, two hours ahead of UTC, rounded up to 15
+    /// minutes.
+    fn build(tasks: &[Task]) -> Timesheet {
+        let config = Config {
+            time_zone: Some(Tz::Europe__Berlin),
+            ..Config::default()
+        };
+
+        Timesheet::build(tasks, &config, Period::week(day(2024, 5, 6)))
+    }
+
+    #[test]
+    fn rounds_each_day_instead_of_each_time_frame() {
+        let tasks = [task(
+            "Website",
+            &[
+                (utc(2024, 5, 6, 7, 0), utc(2024, 5, 6, 7, 7)),
+                (utc(2024, 5, 6, 12, 0), utc(2024, 5, 6, 12, 7)),
+                (utc(2024, 5, 8, 7, 0), utc(2024, 5, 8, 7, 20)),
+            ],
+        )];
+
+        let timesheet = build(&tasks);
+
+        assert_eq!(timesheet.rounding.scope, RoundingScope::Day);
+        assert_eq!(timesheet.rows[0].days, minutes(&[15, 0, 30, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn splits_time_frames_at_local_midnight() {
+        // 23:00 on Tuesday to 01:00 on Wednesday in Berlin, all of it on Tuesday in UTC
+        let tasks = [task(
+            "Website",
+            &[(utc(2024, 5, 7, 21, 0), utc(2024, 5, 7, 23, 0))],
+        )];
+
+        let timesheet = build(&tasks);
+
+        assert_eq!(timesheet.days.len(), 7);
+        assert_eq!(timesheet.days[0], day(2024, 5, 6));
+        assert_eq!(timesheet.rows[0].days, minutes(&[0, 60, 60, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn leaves_out_time_outside_the_period() {
+        let tasks = [
+            task(
+                "Website",
+                &[
+                    // Sunday before the week in Berlin, though Monday in UTC
+                    (utc(2024, 5, 5, 21, 0), utc(2024, 5, 5, 21, 30)),
+                    (utc(2024, 5, 6, 8, 0), utc(2024, 5, 6, 9, 0)),
+                ],
+            ),
+            task(
+                "Mobile app",
+                &[(utc(2024, 5, 1, 8, 0), utc(2024, 5, 1, 9, 0))],
+            ),
+            task("Backend", &[]),
+        ];
+
+        let timesheet = build(&tasks);
+
+        let projects: Vec<_> = timesheet
+            .rows
+            .iter()
+            .map(|row| row.project.as_str())
+            .collect();
+        assert_eq!(projects, ["Website"]);
+        assert_eq!(timesheet.rows[0].days, minutes(&[60, 0, 0, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn adds_up_rows_and_columns() {
+        let tasks = [
+            task(
+                "Website",
+                &[
+                    (utc(2024, 5, 6, 7, 0), utc(2024, 5, 6, 8, 0)),
+                    (utc(2024, 5, 7, 7, 0), utc(2024, 5, 7, 7, 30)),
+                ],
+            ),
+            task(
+                "Backend",
+                &[
+                    (utc(2024, 5, 6, 9, 0), utc(2024, 5, 6, 9, 45)),
+                    (utc(2024, 5, 12, 9, 0), utc(2024, 5, 12, 10, 0)),
+                ],
+            ),
+        ];
+
+        let timesheet = build(&tasks);
+
+        assert_eq!(timesheet.rows[0].total(), Duration::minutes(90));
+        assert_eq!(timesheet.rows[1].total(), Duration::minutes(105));
+        assert_eq!(timesheet.day_totals(), minutes(&[105, 30, 0, 0, 0, 0, 60]));
+        assert_eq!(timesheet.total(), Duration::minutes(195));
+    }
+}
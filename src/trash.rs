@@ -0,0 +1,61 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    storage::{Data, Storage},
+    Error, Task,
+};
+
+/// A deleted project, kept until it is restored or purged.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrashedTask {
+    pub task: Task,
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl TrashedTask {
+    pub fn new(task: Task) -> Self {
+        TrashedTask {
+            task,
+            deleted_at: Utc::now(),
+        }
+    }
+
+    fn is_expired(&self, retention: Duration) -> bool {
+        Utc::now() - self.deleted_at > retention
+    }
+}
+
+impl Data {
+    /// Moves the task with the given ID to the trash, stopping its timer first. Returns the
+    /// position the task had in the task list.
+    pub fn trash_task(&mut self, id: Uuid, merge_gap: Option<Duration>) -> Option<usize> {
+        let position = self.tasks.iter().position(|task| task.id == id)?;
+        let mut task = self.tasks.remove(position);
+        task.stop(merge_gap);
+        self.trash.push(TrashedTask::new(task));
+
+        Some(position)
+    }
+}
+
+/// Permanently removes projects that have been in the trash for longer than `retention`.
+/// Returns how many were purged. The storage is only written if any expired.
+pub fn purge_expired(storage: &dyn Storage, retention: Duration) -> Result<usize, Error> {
+    if !storage
+        .read_data()?
+        .trash
+        .iter()
+        .any(|trashed| trashed.is_expired(retention))
+    {
+        return Ok(0);
+    }
+
+    storage.update_data(|data| {
+        let count = data.trash.len();
+        data.trash.retain(|trashed| !trashed.is_expired(retention));
+
+        count - data.trash.len()
+    })
+}
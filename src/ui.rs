@@ -0,0 +1,1557 @@
+use std::{io, sync::mpsc, thread, time::Instant};
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use crossterm::{
+    cursor,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyEvent,
+        KeyModifiers,
+    },
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table},
+    Terminal,
+};
+
+use crate::{
+    app::{
+        detail_rows, App, DetailRow, FormField, IdleChoice, ProjectField, ProjectForm, ProjectRow,
+        ReportForm, ReportRange, State, StopChoice, StopTimerForm, TimeFrameForm, Transitions,
+        REPORT_RANGES, STOP_CHOICES,
+    },
+    config::Config,
+    format_duration, format_timestamp,
+    render::format_cell,
+    report::format_duration_report,
+    storage::{Repository, Storage},
+    time_zone::local_day,
+    timesheet::Timesheet,
+    trash::TrashedTask,
+    Error, Task, TimeFrame, TIMESTAMP_FORMAT,
+};
+
+const DAY_FORMAT: &str = "%a %Y-%m-%d";
+const TIME_FORMAT: &str = "%H:%M";
+
+enum Event<I> {
+    Input(I),
+    Tick,
+}
+
+/// Puts the terminal back into its normal mode when the TUI exits, including on errors.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            cursor::Show
+        );
+    }
+}
+
+pub fn run(storage: Box<dyn Storage>, config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    enable_raw_mode().expect("can run in raw mode");
+    let _guard = TerminalGuard;
+
+    let (tx, rx) = mpsc::channel();
+    let tick_rate = std::time::Duration::from_millis(200);
+    thread::spawn(move || {
+        let mut last_tick = Instant::now();
+        loop {
+            let timeout = tick_rate
+                .checked_sub(last_tick.elapsed())
+                .unwrap_or_else(|| std::time::Duration::from_secs(0));
+
+            if event::poll(timeout).expect("poll works") {
+                if let CEvent::Key(key) = event::read().expect("can read events") {
+                    tx.send(Event::Input(key)).expect("can send events");
+                }
+            }
+
+            if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
+                last_tick = Instant::now();
+            }
+        }
+    });
+
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    terminal.clear()?;
+
+    let mut app = App::load(Repository::new(storage), config)?;
+
+    loop {
+        // Pick up changes written by other instances, retrying on the next tick if the DB
+        // cannot be read right now
+        if let Err(e) = app.reload_if_changed() {
+            app.show_error(&e);
+        }
+
+        terminal.draw(|rect| {
+            let size = rect.size();
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(2)
+                .constraints(
+                    [
+                        Constraint::Length(3),
+                        Constraint::Min(2),
+                        Constraint::Length(3),
+                    ]
+                    .as_ref(),
+                )
+                .split(size);
+
+            let contextual_help = Paragraph::new("q: Quit | ?: Show help")
+                .style(Style::default().fg(Color::LightCyan))
+                .alignment(Alignment::Center)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .style(Style::default().fg(Color::White))
+                        .title("Shortcuts")
+                        .border_type(BorderType::Plain),
+                );
+
+            rect.render_widget(contextual_help, chunks[0]);
+
+            let copyright =
+                match &app.toast {
+                    Some(toast) if toast.is_error => Paragraph::new(toast.message.as_str())
+                        .style(Style::default().fg(Color::Red)),
+                    Some(toast) => Paragraph::new(toast.message.as_str())
+                        .style(Style::default().fg(Color::Green)),
+                    None => Paragraph::new("Time Tracking CLI")
+                        .style(Style::default().fg(Color::LightCyan)),
+                }
+                .alignment(Alignment::Center)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .style(Style::default().fg(Color::White))
+                        .border_type(BorderType::Plain),
+                );
+
+            rect.render_widget(copyright, chunks[2]);
+
+            match &app.state {
+                State::Projects => {
+                    let task_details = render_tasks(&app.project_rows(), app.tag_filter.as_deref());
+                    rect.render_stateful_widget(task_details, chunks[1], &mut app.task_list_state);
+                }
+                State::ArchivedProjects => {
+                    let archived = render_archived_tasks(&app.archived_tasks());
+                    rect.render_stateful_widget(archived, chunks[1], &mut app.archive_list_state);
+                }
+                State::Trash => {
+                    let trash = render_trash(
+                        &app.trash,
+                        app.config.trash.retention(),
+                        app.config.time_zone(),
+                    );
+                    rect.render_stateful_widget(trash, chunks[1], &mut app.trash_list_state);
+                }
+                State::PurgeTrashed => {
+                    let popup_input_field = render_delete_project_popup();
+                    let area = centered_rect(40, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup_input_field,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 3,
+                            width: area.width,
+                        },
+                    )
+                }
+                State::EditProject { form, .. } => {
+                    let popup = render_project_popup(form);
+                    let area = centered_rect(50, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 6,
+                            width: area.width,
+                        },
+                    );
+                }
+                State::Help => {
+                    let help_popup = render_help_popup();
+                    let area = centered_rect(40, 40, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(help_popup, area);
+                }
+                State::CreateProject { input, error } => {
+                    let popup_input_field = render_create_popup(input, error.as_deref());
+                    let area = centered_rect(40, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup_input_field,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 4,
+                            width: area.width,
+                        },
+                    );
+                }
+                State::EditSubtask {
+                    subtask_id,
+                    input,
+                    error,
+                    ..
+                } => {
+                    let popup = render_subtask_popup(subtask_id.is_some(), input, error.as_deref());
+                    let area = centered_rect(40, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 4,
+                            width: area.width,
+                        },
+                    );
+                }
+                State::DeleteSubtask { .. } => {
+                    let popup = render_delete_subtask_popup(app.subtask_deletion());
+                    let area = centered_rect(50, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 4,
+                            width: area.width,
+                        },
+                    )
+                }
+                State::DeleteProject => {
+                    let popup_input_field = render_delete_project_popup();
+                    let area = centered_rect(40, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup_input_field,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 3,
+                            width: area.width,
+                        },
+                    )
+                }
+                State::TaskDetails { task_id } => {
+                    if let Some(task) = app.tasks.iter().find(|task| task.id == *task_id) {
+                        let time_frames = render_time_frames(task, app.config.time_zone());
+                        rect.render_stateful_widget(
+                            time_frames,
+                            chunks[1],
+                            &mut app.time_frame_list_state,
+                        );
+                    }
+                }
+                State::EditTimeFrame { form, .. } => {
+                    let warning = app.time_frame_form_warning();
+                    let subtask = app
+                        .detail_task()
+                        .and_then(|task| task.subtask(form.subtask_id))
+                        .map_or("", |subtask| subtask.name.as_str());
+                    let popup = render_time_frame_popup(form, subtask, warning);
+                    let area = centered_rect(60, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 6,
+                            width: area.width,
+                        },
+                    );
+                }
+                State::DeleteTimeFrame { .. } => {
+                    let popup_input_field = render_delete_project_popup();
+                    let area = centered_rect(40, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup_input_field,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 3,
+                            width: area.width,
+                        },
+                    )
+                }
+                State::EditNote { input, .. } => {
+                    let popup = render_note_popup(input);
+                    let area = centered_rect(60, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 3,
+                            width: area.width,
+                        },
+                    );
+                }
+                State::LongRunningTimer { task_id, form } => {
+                    if let Some(task) = app.tasks.iter().find(|task| task.id == *task_id) {
+                        let popup = render_long_running_popup(task, form, app.config.time_zone());
+                        let area = centered_rect(60, 20, chunks[1]);
+
+                        rect.render_widget(Clear, chunks[1]);
+                        rect.render_widget(
+                            popup,
+                            Rect {
+                                x: area.x,
+                                y: area.y,
+                                height: STOP_CHOICES.len() as u16 + 5,
+                                width: area.width,
+                            },
+                        );
+                    }
+                }
+                State::Idle { since, until, .. } => {
+                    let popup = render_idle_popup(
+                        *since,
+                        *until,
+                        &app.idle_tasks(*since),
+                        app.config.time_zone(),
+                    );
+                    let area = centered_rect(60, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: 4,
+                            width: area.width,
+                        },
+                    );
+                }
+                State::SearchNotes { query } => {
+                    let results =
+                        render_search_results(query, &app.search_results(), app.config.time_zone());
+                    rect.render_stateful_widget(results, chunks[1], &mut app.search_list_state);
+                }
+                State::Timesheet { .. } => {
+                    if let Some(timesheet) = app.timesheet() {
+                        let (table, widths) = render_timesheet(&timesheet);
+                        rect.render_stateful_widget(
+                            table.widths(&widths),
+                            chunks[1],
+                            &mut app.timesheet_list_state,
+                        );
+                    }
+                }
+                State::Report { form } => {
+                    let popup = render_report_popup(form);
+                    let area = centered_rect(50, 20, chunks[1]);
+
+                    rect.render_widget(Clear, chunks[1]);
+                    rect.render_widget(
+                        popup,
+                        Rect {
+                            x: area.x,
+                            y: area.y,
+                            height: REPORT_RANGES.len() as u16 + 4,
+                            width: area.width,
+                        },
+                    );
+                }
+            }
+        })?;
+
+        let event = rx.recv()?;
+        // A key press ending a time away only brings up the idle popup
+        if matches!(event, Event::Input(_)) && !app.register_input() {
+            continue;
+        }
+
+        match event {
+            Event::Input(key) => match handle_input(&mut app, key) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => app.show_error(&e),
+            },
+            Event::Tick => {
+                app.expire_toast();
+                app.check_idle();
+                if let Err(e) = app.save_activity() {
+                    app.show_error(&e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles a key press, returning whether the TUI should quit.
+fn handle_input(app: &mut App, event: KeyEvent) -> Result<bool, Error> {
+    match &app.state {
+        State::Projects => match event.code {
+            KeyCode::Char('q') => return Ok(true),
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.select_next();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.select_previous();
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                app.toggle_selected()?;
+            }
+            KeyCode::Char('u') => {
+                app.undo()?;
+            }
+            KeyCode::Char('r') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.redo()?;
+            }
+            KeyCode::Char('a') => {
+                app.transition(Transitions::CreateNew);
+            }
+            KeyCode::Char('A') => {
+                app.transition(Transitions::CreateSubtask);
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                app.transition(Transitions::Expand);
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                app.transition(Transitions::Collapse);
+            }
+            KeyCode::Char('d') => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Char('e') => {
+                app.transition(Transitions::Edit);
+            }
+            KeyCode::Char('f') => {
+                app.transition(Transitions::ShowTagFilter);
+            }
+            KeyCode::Char('x') => {
+                app.archive_selected()?;
+            }
+            KeyCode::Char('X') => {
+                app.transition(Transitions::ShowArchived);
+            }
+            KeyCode::Char('b') => {
+                app.transition(Transitions::ShowTrash);
+            }
+            KeyCode::Char('t') => {
+                app.transition(Transitions::ShowDetails);
+            }
+            KeyCode::Char('r') => {
+                app.transition(Transitions::ShowReport);
+            }
+            KeyCode::Char('s') => {
+                app.transition(Transitions::ShowTimesheet);
+            }
+            KeyCode::Char('/') => {
+                app.transition(Transitions::ShowSearch);
+            }
+            KeyCode::Char('?') => {
+                app.transition(Transitions::ShowHelp);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::CreateProject { .. } => match event.code {
+            KeyCode::Enter => {
+                app.create_project()?;
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::EditProject { .. } => match event.code {
+            KeyCode::Enter => {
+                app.save_project()?;
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                app.transition(Transitions::NextField);
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::ArchivedProjects => match event.code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.select_next_archived();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.select_previous_archived();
+            }
+            KeyCode::Char('r') | KeyCode::Enter => {
+                app.restore_selected_archived()?;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::Trash => match event.code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.select_next_trashed();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.select_previous_trashed();
+            }
+            KeyCode::Char('r') | KeyCode::Enter => {
+                app.restore_selected_trashed()?;
+            }
+            KeyCode::Char('d') => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::PurgeTrashed => match event.code {
+            KeyCode::Esc | KeyCode::Char('n' | 'q') => {
+                app.transition(Transitions::Escape);
+            }
+            KeyCode::Char('y') => {
+                app.purge_selected_trashed()?;
+            }
+            _ => {}
+        },
+        State::EditSubtask { .. } => match event.code {
+            KeyCode::Enter => {
+                app.save_subtask()?;
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::DeleteSubtask { .. } => match event.code {
+            KeyCode::Esc | KeyCode::Char('n' | 'q') => {
+                app.transition(Transitions::Escape);
+            }
+            KeyCode::Char('y') => {
+                app.delete_subtask()?;
+            }
+            _ => {}
+        },
+        State::DeleteProject => match event.code {
+            KeyCode::Esc | KeyCode::Char('n' | 'q') => {
+                app.transition(Transitions::Escape);
+            }
+            KeyCode::Char('y') => {
+                app.delete_selected()?;
+
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::Help => match event.code {
+            KeyCode::Esc | KeyCode::Char('?' | 'q') => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::TaskDetails { .. } => match event.code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.select_next_time_frame();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.select_previous_time_frame();
+            }
+            KeyCode::Char('a') => {
+                app.transition(Transitions::CreateNew);
+            }
+            KeyCode::Char('e') | KeyCode::Enter => {
+                app.transition(Transitions::Edit);
+            }
+            KeyCode::Char('d') => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Char('n') => {
+                app.transition(Transitions::EditNote);
+            }
+            KeyCode::Char('u') => {
+                app.undo()?;
+            }
+            KeyCode::Char('r') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.redo()?;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::EditTimeFrame { .. } => match event.code {
+            KeyCode::Enter => {
+                app.save_time_frame()?;
+            }
+            KeyCode::Down => {
+                app.move_form_subtask(1);
+            }
+            KeyCode::Up => {
+                app.move_form_subtask(-1);
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                app.transition(Transitions::NextField);
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::DeleteTimeFrame { .. } => match event.code {
+            KeyCode::Esc | KeyCode::Char('n' | 'q') => {
+                app.transition(Transitions::Escape);
+            }
+            KeyCode::Char('y') => {
+                app.delete_selected_time_frame()?;
+            }
+            _ => {}
+        },
+        State::LongRunningTimer { .. } => match event.code {
+            KeyCode::Enter => {
+                app.resolve_long_running()?;
+            }
+            KeyCode::Down => {
+                app.select_next_stop_choice();
+            }
+            KeyCode::Up => {
+                app.select_previous_stop_choice();
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::Idle { .. } => match event.code {
+            KeyCode::Esc | KeyCode::Char('k') => {
+                app.resolve_idle(IdleChoice::Keep)?;
+            }
+            KeyCode::Char('d') => {
+                app.resolve_idle(IdleChoice::Discard)?;
+            }
+            KeyCode::Char('s') => {
+                app.resolve_idle(IdleChoice::Split)?;
+            }
+            _ => {}
+        },
+        State::EditNote { .. } => match event.code {
+            KeyCode::Enter => {
+                app.save_note()?;
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::SearchNotes { .. } => match event.code {
+            KeyCode::Down => {
+                app.select_next_search_result();
+            }
+            KeyCode::Up => {
+                app.select_previous_search_result();
+            }
+            KeyCode::Enter => {
+                app.show_selected_search_result();
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::Timesheet { .. } => match event.code {
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.select_next_timesheet_day();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.select_previous_timesheet_day();
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                app.next_timesheet_period();
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                app.previous_timesheet_period();
+            }
+            KeyCode::Char('m') => {
+                app.toggle_timesheet_span();
+            }
+            KeyCode::Char('x') => {
+                app.export_timesheet();
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+        State::Report { .. } => match event.code {
+            KeyCode::Enter => {
+                app.generate_report();
+            }
+            KeyCode::Down => {
+                app.select_next_report_range();
+            }
+            KeyCode::Up => {
+                app.select_previous_report_range();
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                app.transition(Transitions::NextField);
+            }
+            KeyCode::Char(c) => {
+                app.transition(Transitions::InputCharacter(c));
+            }
+            KeyCode::Backspace => {
+                app.transition(Transitions::Delete);
+            }
+            KeyCode::Esc => {
+                app.transition(Transitions::Escape);
+            }
+            _ => {}
+        },
+    }
+
+    Ok(false)
+}
+
+fn render_help_popup<'a>() -> Table<'a> {
+    Table::new(vec![
+        Row::new(vec![
+            Cell::from(Span::raw("a")),
+            Cell::from(Span::raw("Add new project")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("A")),
+            Cell::from(Span::raw("Add task to selected project")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("l / h")),
+            Cell::from(Span::raw("Show/hide tasks of selected project")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("d")),
+            Cell::from(Span::raw("Delete selected project or task")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("e")),
+            Cell::from(Span::raw("Edit selected project or task")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("f")),
+            Cell::from(Span::raw("Filter projects by tag")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("x")),
+            Cell::from(Span::raw("Archive selected project")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("X")),
+            Cell::from(Span::raw("Show archived projects")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("<space>")),
+            Cell::from(Span::raw("Start/stop timer of selected task")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("b")),
+            Cell::from(Span::raw("Show deleted projects")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("u")),
+            Cell::from(Span::raw("Undo last change")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("<ctrl-r>")),
+            Cell::from(Span::raw("Redo undone change")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("t")),
+            Cell::from(Span::raw("Show time frames of selected project")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("r")),
+            Cell::from(Span::raw("Generate a report for a period")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("s")),
+            Cell::from(Span::raw("Show timesheet per project and day")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("/")),
+            Cell::from(Span::raw("Search notes of all time frames")),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::raw("<esc>")),
+            Cell::from(Span::raw("Close help")),
+        ]),
+    ])
+    .header(Row::new(vec![
+        Cell::from(Span::raw("Shortcut")),
+        Cell::from(Span::raw("Description")),
+    ]))
+    .widths(&[Constraint::Percentage(20), Constraint::Percentage(80)])
+    .block(Block::default().title("Help").borders(Borders::ALL))
+}
+
+fn render_create_popup<'a>(input: &'a str, error: Option<&'a str>) -> Paragraph<'a> {
+    let message = match error {
+        Some(error) => Span::styled(error, Style::default().fg(Color::Red)),
+        None => Span::raw("<enter>: save"),
+    };
+
+    Paragraph::new(vec![Spans::from(input), Spans::from(message)]).block(
+        Block::default()
+            .title("New project name")
+            .borders(Borders::ALL),
+    )
+}
+
+fn render_subtask_popup<'a>(
+    is_existing: bool,
+    input: &'a str,
+    error: Option<&'a str>,
+) -> Paragraph<'a> {
+    let message = match error {
+        Some(error) => Span::styled(error, Style::default().fg(Color::Red)),
+        None => Span::raw("<enter>: save"),
+    };
+
+    Paragraph::new(vec![Spans::from(input), Spans::from(message)]).block(
+        Block::default()
+            .title(if is_existing {
+                "Rename task"
+            } else {
+                "New task name"
+            })
+            .borders(Borders::ALL),
+    )
+}
+
+fn render_project_popup<'a>(form: &'a ProjectForm) -> Paragraph<'a> {
+    let input_style = |field| {
+        if form.focus == field {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        }
+    };
+
+    let message = match &form.error {
+        Some(error) => Span::styled(error.clone(), Style::default().fg(Color::Red)),
+        None => Span::raw("<tab>: switch field | <enter>: save"),
+    };
+
+    Paragraph::new(vec![
+        Spans::from(vec![
+            Span::raw("Name:   "),
+            Span::styled(form.name.as_str(), input_style(ProjectField::Name)),
+        ]),
+        Spans::from(vec![
+            Span::raw("Client: "),
+            Span::styled(form.client.as_str(), input_style(ProjectField::Client)),
+        ]),
+        Spans::from(vec![
+            Span::raw("Tags:   "),
+            Span::styled(form.tags.as_str(), input_style(ProjectField::Tags)),
+        ]),
+        Spans::from(message),
+    ])
+    .block(Block::default().title("Edit project").borders(Borders::ALL))
+}
+
+fn render_time_frame_popup<'a>(
+    form: &'a TimeFrameForm,
+    subtask: &'a str,
+    warning: Option<String>,
+) -> Paragraph<'a> {
+    let input_style = |field| {
+        if form.focus == field {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        }
+    };
+
+    let message = match (&form.error, warning) {
+        (Some(error), _) => Span::styled(error.clone(), Style::default().fg(Color::Red)),
+        (None, Some(warning)) => Span::styled(warning, Style::default().fg(Color::Yellow)),
+        (None, None) => Span::raw("<tab>: switch field | <up>/<down>: task | <enter>: save"),
+    };
+
+    Paragraph::new(vec![
+        Spans::from(vec![Span::raw("Task:  "), Span::raw(subtask)]),
+        Spans::from(vec![
+            Span::raw("Start: "),
+            Span::styled(form.start.as_str(), input_style(FormField::Start)),
+        ]),
+        Spans::from(vec![
+            Span::raw("End:   "),
+            Span::styled(form.end.as_str(), input_style(FormField::End)),
+        ]),
+        Spans::from(message),
+    ])
+    .block(
+        Block::default()
+            .title(if form.time_frame_id.is_some() {
+                "Edit time frame"
+            } else {
+                "Add time frame"
+            })
+            .borders(Borders::ALL),
+    )
+}
+
+fn render_note_popup<'a>(input: &'a str) -> Paragraph<'a> {
+    Paragraph::new(input).block(
+        Block::default()
+            .title("Note | enter: save, esc: skip")
+            .borders(Borders::ALL),
+    )
+}
+
+fn render_idle_popup<'a>(
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    tasks: &[&Task],
+    time_zone: Tz,
+) -> Paragraph<'a> {
+    let projects: Vec<_> = tasks.iter().map(|task| task.project.as_str()).collect();
+    let lines = vec![
+        Spans::from(format!(
+            "Away since {} ({})",
+            format_timestamp(since, time_zone),
+            format_duration(until - since)
+        )),
+        Spans::from(format!("Running: {}", projects.join(", "))),
+    ];
+
+    Paragraph::new(lines).block(
+        Block::default()
+            .title("Idle | k: keep, d: discard, s: split off")
+            .borders(Borders::ALL),
+    )
+}
+
+fn render_long_running_popup<'a>(
+    task: &Task,
+    form: &'a StopTimerForm,
+    time_zone: Tz,
+) -> Paragraph<'a> {
+    let running_since = task.running_since.unwrap_or_else(Utc::now);
+    let last_activity = match task.recorded_activity() {
+        Some(last_activity) => format_timestamp(last_activity, time_zone),
+        None => "none noticed".to_owned(),
+    };
+
+    let mut lines = vec![
+        Spans::from(format!(
+            "{} / {}",
+            task.project,
+            task.subtask(task.current_subtask())
+                .map_or("", |subtask| subtask.name.as_str())
+        )),
+        Spans::from(format!(
+            "Running since {} ({})",
+            format_timestamp(running_since, time_zone),
+            format_duration(task.current_duration())
+        )),
+    ];
+    lines.extend(STOP_CHOICES.iter().enumerate().map(|(index, choice)| {
+        let is_selected = index == form.selected;
+        let marker = if is_selected { "> " } else { "  " };
+        let label = Span::styled(
+            format!("{}{}", marker, choice.label()),
+            if is_selected {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            },
+        );
+
+        match choice {
+            StopChoice::LastActivity => {
+                Spans::from(vec![label, Span::raw(format!(" ({})", last_activity))])
+            }
+            StopChoice::ChosenTime => Spans::from(vec![
+                label,
+                Span::raw(": "),
+                Span::styled(
+                    form.stop_at.as_str(),
+                    if is_selected {
+                        Style::default().add_modifier(Modifier::REVERSED)
+                    } else {
+                        Style::default()
+                    },
+                ),
+            ]),
+            StopChoice::Keep => Spans::from(label),
+        }
+    }));
+    lines.push(Spans::from(match &form.error {
+        Some(error) => Span::styled(error.clone(), Style::default().fg(Color::Red)),
+        None => Span::raw("<up>/<down>: select | <enter>: confirm"),
+    }));
+
+    Paragraph::new(lines).block(
+        Block::default()
+            .title("Timer left running")
+            .borders(Borders::ALL),
+    )
+}
+
+fn render_report_popup<'a>(form: &'a ReportForm) -> Paragraph<'a> {
+    let is_custom = form.range() == ReportRange::Custom;
+    let input_style = |field| {
+        if is_custom && form.focus == field {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        }
+    };
+
+    let mut lines: Vec<_> = REPORT_RANGES
+        .iter()
+        .enumerate()
+        .map(|(index, range)| {
+            if index == form.selected {
+                Spans::from(Span::styled(
+                    format!("> {}", range.label()),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ))
+            } else {
+                Spans::from(Span::raw(format!("  {}", range.label())))
+            }
+        })
+        .collect();
+
+    lines.push(Spans::from(vec![
+        Span::raw("From: "),
+        Span::styled(form.from.as_str(), input_style(FormField::Start)),
+        Span::raw("  To: "),
+        Span::styled(form.to.as_str(), input_style(FormField::End)),
+    ]));
+    lines.push(Spans::from(match &form.error {
+        Some(error) => Span::styled(error.clone(), Style::default().fg(Color::Red)),
+        None if is_custom => Span::raw("<tab>: switch field | <enter>: generate"),
+        None => Span::raw("<up>/<down>: select | <enter>: generate"),
+    }));
+
+    Paragraph::new(lines).block(Block::default().title("Report").borders(Borders::ALL))
+}
+
+fn render_delete_project_popup<'a>() -> Paragraph<'a> {
+    Paragraph::new(Span::raw("y/n")).block(
+        Block::default()
+            .title("Confirm deletion")
+            .borders(Borders::ALL),
+    )
+}
+
+fn render_delete_subtask_popup<'a>(deletion: Option<(usize, &str)>) -> Paragraph<'a> {
+    let consequence = match deletion {
+        Some((count, remaining)) => format!("Its {} time frames move to {}", count, remaining),
+        None => Error::LastSubtask.to_string(),
+    };
+
+    Paragraph::new(vec![Spans::from(consequence), Spans::from("y/n")]).block(
+        Block::default()
+            .title("Confirm deletion")
+            .borders(Borders::ALL),
+    )
+}
+
+/// Shows projects with their subtasks below them if expanded, with the times of subtasks
+/// adding up to their project's.
+fn render_tasks<'a>(rows: &[ProjectRow], tag_filter: Option<&str>) -> Table<'a> {
+    let status = |is_running: bool, task: &Task| {
+        if is_running {
+            Span::styled(
+                format!("Running [{}]", format_duration(task.current_duration())),
+                Style::default().fg(Color::Green),
+            )
+        } else {
+            Span::raw("Not running")
+        }
+    };
+
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| match row {
+            ProjectRow::Project { task, expanded } => Row::new(vec![
+                Cell::from(Span::raw(format!(
+                    "{} {}",
+                    if *expanded { "▾" } else { "▸" },
+                    task.project
+                ))),
+                Cell::from(Span::raw(task.client.clone().unwrap_or_default())),
+                Cell::from(Span::raw(task.tags.join(", "))),
+                Cell::from(status(task.is_running(), task)),
+                Cell::from(Span::raw(format_duration(task.total_duration()))),
+            ]),
+            ProjectRow::Subtask(task, subtask) => Row::new(vec![
+                Cell::from(Span::raw(format!("    {}", subtask.name))),
+                Cell::from(""),
+                Cell::from(""),
+                Cell::from(status(task.is_running_subtask(subtask.id), task)),
+                Cell::from(Span::raw(format_duration(
+                    task.subtask_duration(subtask.id),
+                ))),
+            ]),
+        })
+        .collect();
+
+    let task_details = Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "Project",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Client",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Tags",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Status",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Total",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(match tag_filter {
+                    Some(tag) => format!("Details | tag: {} (f: next tag)", tag),
+                    None => "Details".to_owned(),
+                })
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(25),
+            Constraint::Percentage(15),
+            Constraint::Percentage(20),
+            Constraint::Percentage(25),
+            Constraint::Percentage(15),
+        ])
+        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)));
+
+    task_details
+}
+
+fn render_archived_tasks<'a>(tasks: &[&Task]) -> Table<'a> {
+    let rows: Vec<_> = tasks
+        .iter()
+        .map(|task| {
+            Row::new(vec![
+                Cell::from(Span::raw(task.project.clone())),
+                Cell::from(Span::raw(format_duration(task.total_duration()))),
+            ])
+        })
+        .collect();
+
+    Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "Project",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Total",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Archived projects | r: restore, esc: back")
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[Constraint::Percentage(66), Constraint::Percentage(33)])
+        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)))
+}
+
+fn render_trash<'a>(
+    trash: &[TrashedTask],
+    retention: Option<Duration>,
+    time_zone: Tz,
+) -> Table<'a> {
+    let rows: Vec<_> = trash
+        .iter()
+        .map(|trashed| {
+            let purged_on = match retention {
+                Some(retention) => local_day(trashed.deleted_at + retention, time_zone)
+                    .format(DAY_FORMAT)
+                    .to_string(),
+                None => "never".to_owned(),
+            };
+
+            Row::new(vec![
+                Cell::from(Span::raw(trashed.task.project.clone())),
+                Cell::from(Span::raw(format_duration(trashed.task.total_duration()))),
+                Cell::from(Span::raw(format_timestamp(trashed.deleted_at, time_zone))),
+                Cell::from(Span::raw(purged_on)),
+            ])
+        })
+        .collect();
+
+    Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "Project",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Total",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Deleted",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Purged on",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Trash | r: restore, d: delete permanently, esc: back")
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(30),
+            Constraint::Percentage(20),
+            Constraint::Percentage(30),
+            Constraint::Percentage(20),
+        ])
+        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)))
+}
+
+fn render_time_frames<'a>(task: &Task, time_zone: Tz) -> Table<'a> {
+    let rows: Vec<_> = detail_rows(task, time_zone)
+        .into_iter()
+        .map(|row| match row {
+            DetailRow::TimeFrame(index) => {
+                let time_frame = &task.times[index];
+                let start_time = time_frame.start_time.with_timezone(&time_zone);
+                let end_time = time_frame.end_time.with_timezone(&time_zone);
+                let end_format = if end_time.date_naive() == start_time.date_naive() {
+                    TIME_FORMAT
+                } else {
+                    TIMESTAMP_FORMAT
+                };
+
+                Row::new(vec![
+                    Cell::from(Span::raw(start_time.format(DAY_FORMAT).to_string())),
+                    Cell::from(Span::raw(start_time.format(TIME_FORMAT).to_string())),
+                    Cell::from(Span::raw(end_time.format(end_format).to_string())),
+                    Cell::from(Span::raw(format_duration(time_frame.duration()))),
+                    Cell::from(Span::raw(
+                        task.subtask(time_frame.subtask_id)
+                            .map_or_else(String::new, |subtask| subtask.name.clone()),
+                    )),
+                    Cell::from(Span::raw(time_frame.note.clone().unwrap_or_default())),
+                ])
+            }
+            DetailRow::DayTotal { day, total } => Row::new(vec![
+                Cell::from(Span::raw(format!("Total {}", day.format(DAY_FORMAT)))),
+                Cell::from(""),
+                Cell::from(""),
+                Cell::from(Span::raw(format_duration(total))),
+                Cell::from(""),
+                Cell::from(""),
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        })
+        .collect();
+
+    Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "Day",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Start",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "End",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Duration",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Task",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Note",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!(
+                    "{} | a: add, e: edit, n: note, d: delete, u: undo, esc: back",
+                    task.project
+                ))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(22),
+            Constraint::Percentage(8),
+            Constraint::Percentage(17),
+            Constraint::Percentage(10),
+            Constraint::Percentage(15),
+            Constraint::Percentage(28),
+        ])
+        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)))
+}
+
+fn render_search_results<'a>(
+    query: &str,
+    results: &[(&Task, &TimeFrame)],
+    time_zone: Tz,
+) -> Table<'a> {
+    let rows: Vec<_> = results
+        .iter()
+        .map(|(task, time_frame)| {
+            Row::new(vec![
+                Cell::from(Span::raw(task.project.clone())),
+                Cell::from(Span::raw(format_timestamp(
+                    time_frame.start_time,
+                    time_zone,
+                ))),
+                Cell::from(Span::raw(format_duration(time_frame.duration()))),
+                Cell::from(Span::raw(time_frame.note.clone().unwrap_or_default())),
+            ])
+        })
+        .collect();
+
+    Table::new(rows)
+        .header(Row::new(vec![
+            Cell::from(Span::styled(
+                "Project",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Start",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Duration",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Cell::from(Span::styled(
+                "Note",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!(
+                    "Search notes: {}_ | enter: show time frame, esc: back",
+                    query
+                ))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(20),
+            Constraint::Percentage(20),
+            Constraint::Percentage(10),
+            Constraint::Percentage(50),
+        ])
+        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)))
+}
+
+/// Shows the timesheet with a row per day and a column per project, so that months fit on
+/// screen. Returns the column widths along with the table, as the table borrows them.
+fn render_timesheet<'a>(timesheet: &Timesheet) -> (Table<'a>, Vec<Constraint>) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+
+    let mut header = vec![Cell::from(Span::styled("Day", bold))];
+    header.extend(
+        timesheet
+            .rows
+            .iter()
+            .map(|row| Cell::from(Span::styled(row.project.clone(), bold))),
+    );
+    header.push(Cell::from(Span::styled("Total", bold)));
+
+    let mut rows: Vec<_> = timesheet
+        .days
+        .iter()
+        .zip(timesheet.day_totals())
+        .enumerate()
+        .map(|(index, (day, total))| {
+            let mut cells = vec![Cell::from(Span::raw(day.format(DAY_FORMAT).to_string()))];
+            cells.extend(
+                timesheet
+                    .rows
+                    .iter()
+                    .map(|row| Cell::from(Span::raw(format_cell(row.days[index])))),
+            );
+            cells.push(Cell::from(Span::raw(format_cell(total))));
+
+            Row::new(cells)
+        })
+        .collect();
+
+    let mut totals = vec![Cell::from(Span::raw("Total"))];
+    totals.extend(
+        timesheet
+            .rows
+            .iter()
+            .map(|row| Cell::from(Span::raw(format_duration_report(row.total())))),
+    );
+    totals.push(Cell::from(Span::raw(format_duration_report(
+        timesheet.total(),
+    ))));
+    rows.push(Row::new(totals).style(bold));
+
+    let mut widths = vec![Constraint::Length(16)];
+    widths.extend(
+        timesheet
+            .rows
+            .iter()
+            .map(|row| Constraint::Length(row.project.chars().count().clamp(5, 20) as u16 + 1)),
+    );
+    widths.push(Constraint::Length(6));
+
+    let table = Table::new(rows)
+        .header(Row::new(header))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!(
+                    "Timesheet {} | h/l: previous/next, m: week/month, x: export, esc: back",
+                    timesheet.period
+                ))
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(Style::default().bg(Color::Rgb(60, 60, 60)));
+
+    (table, widths)
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, rect: Rect) -> Rect {
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(rect);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(popup_layout[1])[1]
+}